@@ -0,0 +1,322 @@
+use serde_json::Value;
+
+/*
+   One step of a data path: the key of an object, or the index of an array item, negative
+   indices counting from the end. Keys made of digits (`people.0`) index arrays too.
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub enum Segment {
+    Key(String),
+    Index(i64),
+}
+
+/*
+   A path to a value of the template data, as written in placeholders and attributes:
+
+   - `section.title`: object keys, separated by dots
+   - `people.0.name`, `people[0].name`: array items by index
+   - `people[-1]`, `people.-1`: array items from the end, `-1` being the last one
+   - `labels["first name"]`, `labels['a.b']`: quoted keys, for keys with dots, spaces or
+     brackets; quotes and backslashes are escaped with a backslash
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct DataPath {
+    source: String,
+    segments: Vec<Segment>,
+    // Where each segment ends in `source`, to name the value a failing segment applies to.
+    ends: Vec<usize>,
+}
+
+impl DataPath {
+    /*
+       @param path: &str
+       @return Result<DataPath, String>
+
+       Parses a path. Errors give the column of the problem in the path.
+    */
+    pub fn parse(path: &str) -> Result<Self, String> {
+        let source = path.trim();
+        let bytes = source.as_bytes();
+        let mut path = DataPath {
+            source: source.to_string(),
+            segments: Vec::new(),
+            ends: Vec::new(),
+        };
+
+        let mut index = 0;
+        loop {
+            let segment = match bytes.get(index) {
+                Some(b'[') => path.parse_bracket(index)?,
+                Some(b'.') if !path.segments.is_empty() => path.parse_key(index + 1)?,
+                Some(_) if path.segments.is_empty() => path.parse_key(index)?,
+                Some(_) => return Err(path.error("expected `.` or `[`", index)),
+                None if path.segments.is_empty() => return Err(path.error("empty path", 0)),
+                None => break,
+            };
+            index = segment.1;
+            path.segments.push(segment.0);
+            path.ends.push(index);
+        }
+        Ok(path)
+    }
+
+    // A dotted key starting at `start`, and where it ends.
+    fn parse_key(&self, start: usize) -> Result<(Segment, usize), String> {
+        let end = self.source[start..]
+            .find(['.', '[', ']'])
+            .map_or(self.source.len(), |offset| start + offset);
+        if self.source.as_bytes().get(end) == Some(&b']') {
+            return Err(self.error("unexpected `]`", end));
+        }
+        let key = self.source[start..end].trim();
+        if key.is_empty() {
+            return Err(self.error("expected a key", start));
+        }
+        Ok((Segment::Key(key.to_string()), end))
+    }
+
+    // An index or a quoted key in brackets, `start` being the `[`, and where it ends.
+    fn parse_bracket(&self, start: usize) -> Result<(Segment, usize), String> {
+        let close = |index: usize| -> Result<usize, String> {
+            let offset = self.source[index..].len() - self.source[index..].trim_start().len();
+            match self.source.as_bytes().get(index + offset) {
+                Some(b']') => Ok(index + offset + 1),
+                _ => Err(self.error("expected `]`", index + offset)),
+            }
+        };
+
+        let content = &self.source[start + 1..];
+        let offset = content.len() - content.trim_start().len();
+        let quote_start = start + 1 + offset;
+        match content.trim_start().chars().next() {
+            Some(quote @ ('"' | '\'')) => {
+                let mut key = String::new();
+                let mut chars = self.source[quote_start + 1..].char_indices();
+                while let Some((position, ch)) = chars.next() {
+                    match ch {
+                        '\\' => match chars.next() {
+                            Some((_, escaped)) => key.push(escaped),
+                            None => break,
+                        },
+                        ch if ch == quote => {
+                            let end = close(quote_start + 1 + position + 1)?;
+                            return Ok((Segment::Key(key), end));
+                        }
+                        ch => key.push(ch),
+                    }
+                }
+                Err(self.error("unclosed quote", quote_start))
+            }
+            _ => {
+                let end = content
+                    .find(']')
+                    .ok_or_else(|| self.error("unclosed `[`", start))?;
+                let index = content[..end].trim();
+                match index.parse::<i64>() {
+                    Ok(index) => Ok((Segment::Index(index), start + 1 + end + 1)),
+                    Err(_) => Err(self.error(
+                        &format!(
+                            "expected an index or a quoted key in brackets, found `{}`",
+                            index
+                        ),
+                        quote_start,
+                    )),
+                }
+            }
+        }
+    }
+
+    fn error(&self, reason: &str, index: usize) -> String {
+        format!(
+            "invalid path '{}': {} at column {}",
+            self.source,
+            reason,
+            self.source[..index.min(self.source.len())].chars().count() + 1
+        )
+    }
+
+    pub fn segments(&self) -> &[Segment] {
+        &self.segments
+    }
+
+    /*
+       @param data: &Value
+       @return Result<&Value, String>
+
+       The value at the path in `data`. Errors name the segment that couldn't be followed
+       and the value it was applied to.
+    */
+    pub fn lookup<'a>(&self, data: &'a Value) -> Result<&'a Value, String> {
+        let mut value = data;
+        for (position, segment) in self.segments.iter().enumerate() {
+            let parent = match position {
+                0 => "the data".to_string(),
+                _ => format!("'{}'", &self.source[..self.ends[position - 1]]),
+            };
+            let index = match segment {
+                Segment::Index(index) => Some(*index),
+                Segment::Key(key) => key.parse::<i64>().ok(),
+            };
+            let found = match (segment, value, index) {
+                (Segment::Key(key), Value::Object(object), _) => object
+                    .get(key)
+                    .ok_or_else(|| format!("no key '{}' in {}", key, parent)),
+                (_, Value::Array(items), Some(index)) => {
+                    let position = if index < 0 {
+                        items.len() as i64 + index
+                    } else {
+                        index
+                    };
+                    usize::try_from(position)
+                        .ok()
+                        .and_then(|position| items.get(position))
+                        .ok_or_else(|| {
+                            format!(
+                                "index {} is out of bounds, {} has {} items",
+                                index,
+                                parent,
+                                items.len()
+                            )
+                        })
+                }
+                (Segment::Key(key), value, _) => Err(format!(
+                    "can't get '{}' of {}, it is {} and not an object",
+                    key,
+                    parent,
+                    type_name(value)
+                )),
+                (Segment::Index(index), value, _) => Err(format!(
+                    "can't get item {} of {}, it is {} and not an array",
+                    index,
+                    parent,
+                    type_name(value)
+                )),
+            };
+            value = found.map_err(|reason| {
+                format!(
+                    "Path '{}' not found in JSON template: {}",
+                    self.source, reason
+                )
+            })?;
+        }
+        Ok(value)
+    }
+}
+
+/*
+   @param data: &Value
+   @param path: &str
+   @return Result<&Value, String>
+
+   Parses `path` and returns the value at it in `data`.
+*/
+pub fn lookup<'a>(data: &'a Value, path: &str) -> Result<&'a Value, String> {
+    DataPath::parse(path)?.lookup(data)
+}
+
+// The JSON type of a value, for error messages.
+pub fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod data_path_tests {
+    use serde_json::json;
+
+    use crate::data_path::{lookup, DataPath, Segment};
+
+    #[test]
+    fn test_parse_paths() {
+        let key = |key: &str| Segment::Key(key.to_string());
+        let parse = |path: &str| DataPath::parse(path).map(|path| path.segments().to_vec());
+
+        assert_eq!(
+            parse("people[0].name"),
+            Ok(vec![key("people"), Segment::Index(0), key("name")])
+        );
+        assert_eq!(parse("people.-1"), Ok(vec![key("people"), key("-1")]));
+        assert_eq!(
+            parse(r#"labels[ "first name" ]['a.b'][-2]"#),
+            Ok(vec![
+                key("labels"),
+                key("first name"),
+                key("a.b"),
+                Segment::Index(-2)
+            ])
+        );
+        assert_eq!(parse(r#"["say \"hi\""]"#), Ok(vec![key(r#"say "hi""#)]));
+        assert_eq!(
+            parse("people[0"),
+            Err("invalid path 'people[0': unclosed `[` at column 7".to_string())
+        );
+        assert_eq!(
+            parse("people..name"),
+            Err("invalid path 'people..name': expected a key at column 8".to_string())
+        );
+        assert_eq!(
+            parse("people[first]"),
+            Err(
+                "invalid path 'people[first]': expected an index or a quoted key in brackets, found `first` at column 8"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            parse("people['a'b]"),
+            Err("invalid path 'people['a'b]': expected `]` at column 11".to_string())
+        );
+        assert!(parse("").is_err());
+    }
+
+    #[test]
+    fn test_lookup() {
+        let data = json!({
+            "people": [{ "name": "Ada" }, { "name": "Grace" }],
+            "labels": { "first name": "First", "a.b": "Dotted" },
+            "title": "Hi"
+        });
+
+        assert_eq!(lookup(&data, "people[1].name"), Ok(&json!("Grace")));
+        assert_eq!(lookup(&data, "people.0.name"), Ok(&json!("Ada")));
+        assert_eq!(lookup(&data, "people[-1].name"), Ok(&json!("Grace")));
+        assert_eq!(lookup(&data, "people.-2.name"), Ok(&json!("Ada")));
+        assert_eq!(
+            lookup(&data, r#"labels["first name"]"#),
+            Ok(&json!("First"))
+        );
+        assert_eq!(lookup(&data, "labels['a.b']"), Ok(&json!("Dotted")));
+
+        assert_eq!(
+            lookup(&data, "people[2].name"),
+            Err("Path 'people[2].name' not found in JSON template: index 2 is out of bounds, 'people' has 2 items".to_string())
+        );
+        assert_eq!(
+            lookup(&data, "people[0].age"),
+            Err(
+                "Path 'people[0].age' not found in JSON template: no key 'age' in 'people[0]'"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            lookup(&data, "title.text"),
+            Err("Path 'title.text' not found in JSON template: can't get 'text' of 'title', it is string and not an object".to_string())
+        );
+        assert_eq!(
+            lookup(&data, "labels[0]"),
+            Err("Path 'labels[0]' not found in JSON template: can't get item 0 of 'labels', it is object and not an array".to_string())
+        );
+        assert_eq!(
+            lookup(&data, "missing"),
+            Err(
+                "Path 'missing' not found in JSON template: no key 'missing' in the data"
+                    .to_string()
+            )
+        );
+    }
+}
@@ -0,0 +1,479 @@
+use std::fmt;
+use std::io::IsTerminal;
+
+use serde_json::{json, Value};
+
+use crate::html::escape;
+use crate::lexer::{EToken, Span, Token};
+
+// Where something is in the template source: 1-based line and column, and byte range.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Location {
+    pub line: usize,
+    pub column: usize,
+    pub span: Span,
+}
+
+impl Location {
+    // The empty location just past the last character of `source`.
+    pub fn end_of(source: &str) -> Self {
+        let line_start = source.rfind('\n').map(|index| index + 1).unwrap_or(0);
+        Location {
+            line: source.matches('\n').count() + 1,
+            column: source[line_start..].chars().count() + 1,
+            span: Span {
+                start: source.len(),
+                end: source.len(),
+            },
+        }
+    }
+
+    // The empty location just past `text`, when `text` is the source starting here.
+    pub fn after(&self, text: &str) -> Self {
+        let end = Location::end_of(text);
+        let start = self.span.start + text.len();
+        Location {
+            line: self.line + end.line - 1,
+            column: if end.line == 1 {
+                self.column + end.column - 1
+            } else {
+                end.column
+            },
+            span: Span { start, end: start },
+        }
+    }
+}
+
+impl From<&Token> for Location {
+    fn from(token: &Token) -> Self {
+        Location {
+            line: token.line,
+            column: token.column,
+            span: token.span,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    UnexpectedToken {
+        expected: Option<String>,
+        found: String,
+    },
+    UnexpectedEndOfInput {
+        expected: Option<String>,
+    },
+    MissingAttributeValue {
+        attribute: String,
+    },
+    DuplicateAttribute {
+        attribute: String,
+    },
+    UnclosedTag {
+        tag: String,
+    },
+    UnexpectedClosingTag {
+        tag: String,
+    },
+    MismatchedClosingTag {
+        expected: String,
+        found: String,
+    },
+}
+
+/*
+   A syntax error in a template. `location` is where the problem was found; for unclosed
+   and mismatched tags `open_tag` points at the name of the tag that was left open.
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub location: Location,
+    pub open_tag: Option<Box<Location>>,
+}
+
+impl ParseError {
+    pub fn new(kind: ParseErrorKind, location: Location) -> Self {
+        ParseError {
+            kind,
+            location,
+            open_tag: None,
+        }
+    }
+
+    pub fn unexpected_token(token: &Token, expected: Option<&str>) -> Self {
+        ParseError::new(
+            ParseErrorKind::UnexpectedToken {
+                expected: expected.map(str::to_string),
+                found: describe_token(&token.token),
+            },
+            Location::from(token),
+        )
+    }
+
+    pub fn with_open_tag(mut self, open_tag: Location) -> Self {
+        self.open_tag = Some(Box::new(open_tag));
+        self
+    }
+
+    pub fn message(&self) -> String {
+        match &self.kind {
+            ParseErrorKind::UnexpectedToken {
+                expected: Some(expected),
+                found,
+            } => format!("unexpected {}, expected {}", found, expected),
+            ParseErrorKind::UnexpectedToken {
+                expected: None,
+                found,
+            } => format!("unexpected {}", found),
+            ParseErrorKind::UnexpectedEndOfInput {
+                expected: Some(expected),
+            } => format!("unexpected end of input, expected {}", expected),
+            ParseErrorKind::UnexpectedEndOfInput { expected: None } => {
+                "unexpected end of input".to_string()
+            }
+            ParseErrorKind::MissingAttributeValue { attribute } => {
+                format!("attribute `{}` has no value after `=`", attribute)
+            }
+            ParseErrorKind::DuplicateAttribute { attribute } => {
+                format!("duplicate attribute `{}`", attribute)
+            }
+            ParseErrorKind::UnclosedTag { tag } => {
+                format!("unclosed tag `<{}>`, expected `</{}>`", tag, tag)
+            }
+            ParseErrorKind::UnexpectedClosingTag { tag } => {
+                format!(
+                    "unexpected closing tag `</{}>`, no `<{}>` is open",
+                    tag, tag
+                )
+            }
+            ParseErrorKind::MismatchedClosingTag { expected, found } => format!(
+                "mismatched closing tag `</{}>`, expected `</{}>`",
+                found, expected
+            ),
+        }
+    }
+
+    // The error as JSON, for the render API.
+    pub fn to_json(&self) -> Value {
+        let mut error = json!({
+            "type": "parse",
+            "message": self.message(),
+            "line": self.location.line,
+            "column": self.location.column,
+        });
+        if let Some(open_tag) = &self.open_tag {
+            error["openTag"] = json!({ "line": open_tag.line, "column": open_tag.column });
+        }
+        error
+    }
+
+    /*
+       @param source: &str
+       @param file_name: &str
+       @param colour: bool
+       @return String
+
+       Formats the error with the offending source line and carets under the problem,
+       followed by the line where the tag was opened for unclosed and mismatched tags.
+    */
+    pub fn render(&self, source: &str, file_name: &str, colour: bool) -> String {
+        let mut lines = vec![self.location.line];
+        if let Some(open_tag) = &self.open_tag {
+            lines.push(open_tag.line);
+        }
+        let gutter = lines.iter().max().unwrap_or(&1).to_string().len();
+        let margin = paint(&format!("{} |", " ".repeat(gutter)), BLUE, colour);
+
+        let mut output = format!(
+            "{}: {}\n{}{} {}:{}:{}\n{}\n",
+            paint("error", RED, colour),
+            paint(&self.message(), BOLD, colour),
+            " ".repeat(gutter),
+            paint("-->", BLUE, colour),
+            file_name,
+            self.location.line,
+            self.location.column,
+            margin
+        );
+        output.push_str(&snippet(
+            source,
+            &self.location,
+            '^',
+            "",
+            RED,
+            gutter,
+            colour,
+        ));
+
+        if let Some(open_tag) = &self.open_tag {
+            let tag = match &self.kind {
+                ParseErrorKind::UnclosedTag { tag } => tag,
+                ParseErrorKind::MismatchedClosingTag { expected, .. } => expected,
+                _ => "tag",
+            };
+            output.push_str(&format!("{}\n", margin));
+            output.push_str(&snippet(
+                source,
+                open_tag,
+                '-',
+                &format!("`<{}>` opened here", tag),
+                BLUE,
+                gutter,
+                colour,
+            ));
+        }
+        output
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} at line {}, column {}",
+            self.message(),
+            self.location.line,
+            self.location.column
+        )
+    }
+}
+
+/*
+   Errors reported while loading a template: every syntax error in the markup, or an
+   invalid template section (duplicated sections, malformed preview data or schema).
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub enum SlabkitError {
+    Parse(Vec<ParseError>),
+    Template(String),
+}
+
+impl SlabkitError {
+    pub fn render(&self, source: &str, file_name: &str, colour: bool) -> String {
+        match self {
+            SlabkitError::Parse(errors) => {
+                let mut output: Vec<String> = errors
+                    .iter()
+                    .map(|error| error.render(source, file_name, colour))
+                    .collect();
+                if errors.len() > 1 {
+                    output.push(format!(
+                        "{}: {} errors found in {}\n",
+                        paint("error", RED, colour),
+                        errors.len(),
+                        file_name
+                    ));
+                }
+                output.join("\n")
+            }
+            SlabkitError::Template(message) => format!("{}\n", message),
+        }
+    }
+
+    // Every error as JSON, for the render API.
+    pub fn to_json(&self) -> Vec<Value> {
+        match self {
+            SlabkitError::Parse(errors) => errors.iter().map(ParseError::to_json).collect(),
+            SlabkitError::Template(message) => vec![json!({
+                "type": "template",
+                "message": message.trim_start_matches("Slabkit error: "),
+            })],
+        }
+    }
+}
+
+impl fmt::Display for SlabkitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlabkitError::Parse(errors) => {
+                let errors: Vec<String> = errors.iter().map(ParseError::to_string).collect();
+                write!(f, "Slabkit error: {}", errors.join("; "))
+            }
+            SlabkitError::Template(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl From<Vec<ParseError>> for SlabkitError {
+    fn from(errors: Vec<ParseError>) -> Self {
+        SlabkitError::Parse(errors)
+    }
+}
+
+// Colour diagnostics only when they go to a terminal and NO_COLOR isn't set.
+pub fn use_colour() -> bool {
+    std::io::stderr().is_terminal() && std::env::var_os("NO_COLOR").is_none()
+}
+
+const RED: &str = "1;31";
+const BLUE: &str = "1;34";
+const BOLD: &str = "1";
+
+fn paint(text: &str, code: &str, colour: bool) -> String {
+    if colour {
+        format!("\x1b[{}m{}\x1b[0m", code, text)
+    } else {
+        text.to_string()
+    }
+}
+
+// Removes the colours of a coloured report.
+pub fn strip_colour(report: &str) -> String {
+    let mut parts = report.split("\x1b[");
+    let mut plain = parts.next().unwrap_or_default().to_string();
+    for part in parts {
+        plain.push_str(part.split_once('m').map(|(_, text)| text).unwrap_or(part));
+    }
+    plain
+}
+
+/*
+   @param report: &str
+   @return String
+
+   Escapes a coloured report for HTML, turning its colours into `<span>`s with the
+   classes `slk-error`, `slk-note` and `slk-bold`.
+*/
+pub fn colour_to_html(report: &str) -> String {
+    let mut parts = report.split("\x1b[");
+    let mut html = escape(parts.next().unwrap_or_default());
+    for part in parts {
+        let (code, text) = part.split_once('m').unwrap_or(("", part));
+        match code {
+            RED => html.push_str(r#"<span class="slk-error">"#),
+            BLUE => html.push_str(r#"<span class="slk-note">"#),
+            BOLD => html.push_str(r#"<span class="slk-bold">"#),
+            "0" => html.push_str("</span>"),
+            _ => {}
+        }
+        html.push_str(&escape(text));
+    }
+    html
+}
+
+fn describe_token(token: &EToken) -> String {
+    match token {
+        EToken::TEXT(text) if text.trim().is_empty() => "whitespace".to_string(),
+        EToken::TEXT(text) => {
+            let text = text.trim();
+            match text.char_indices().nth(20) {
+                Some((end, _)) => format!("text `{}...`", &text[..end]),
+                None => format!("text `{}`", text),
+            }
+        }
+        token => format!("`{}`", token.to_symbol()),
+    }
+}
+
+// The source line of `location` with `marker`s under its span (at least one character).
+fn snippet(
+    source: &str,
+    location: &Location,
+    marker: char,
+    label: &str,
+    code: &str,
+    gutter: usize,
+    colour: bool,
+) -> String {
+    let line = source.lines().nth(location.line - 1).unwrap_or("");
+    let column = location.column.max(1);
+    let available = line.chars().count().saturating_sub(column - 1);
+    let width = source
+        .get(location.span.start..location.span.end)
+        .map(|text| text.chars().take_while(|ch| *ch != '\n').count())
+        .unwrap_or(0)
+        .min(available)
+        .max(1);
+
+    let mut markers = marker.to_string().repeat(width);
+    if !label.is_empty() {
+        markers = format!("{} {}", markers, label);
+    }
+    format!(
+        "{} {}\n{} {}{}\n",
+        paint(&format!("{:>gutter$} |", location.line), BLUE, colour),
+        line,
+        paint(&format!("{} |", " ".repeat(gutter)), BLUE, colour),
+        " ".repeat(column - 1),
+        paint(&markers, code, colour)
+    )
+}
+
+#[cfg(test)]
+mod error_tests {
+    use serde_json::json;
+
+    use crate::error::{colour_to_html, strip_colour, SlabkitError};
+    use crate::parser::Parser;
+
+    #[test]
+    fn test_render_mismatched_tag() {
+        let source = "<div>\n  <p>Hello</div>";
+        let error = Parser::new(source.to_string()).parse().unwrap_err();
+
+        assert_eq!(
+            error.render(source, "hero.html", false),
+            "error: mismatched closing tag `</div>`, expected `</p>`
+ --> hero.html:2:13
+  |
+2 |   <p>Hello</div>
+  |             ^^^
+  |
+2 |   <p>Hello</div>
+  |    - `<p>` opened here
+"
+        );
+    }
+
+    #[test]
+    fn test_errors_to_json() {
+        let source = "<div>\n  <p>Hello</div>";
+        let error = SlabkitError::Parse(Parser::new(source.to_string()).parse_nodes().unwrap_err());
+
+        assert_eq!(
+            error.to_json(),
+            vec![json!({
+                "type": "parse",
+                "message": "mismatched closing tag `</div>`, expected `</p>`",
+                "line": 2,
+                "column": 13,
+                "openTag": { "line": 2, "column": 4 },
+            })]
+        );
+        assert_eq!(
+            SlabkitError::Template("Slabkit error: invalid slk-previewdata".to_string()).to_json(),
+            vec![json!({ "type": "template", "message": "invalid slk-previewdata" })]
+        );
+    }
+
+    #[test]
+    fn test_render_with_colour() {
+        let source = "<div>";
+        let error = Parser::new(source.to_string()).parse().unwrap_err();
+        let output = error.render(source, "hero.html", true);
+
+        assert!(output.starts_with("\x1b[1;31merror\x1b[0m: "));
+        assert!(output.contains("unclosed tag `<div>`"));
+    }
+
+    #[test]
+    fn test_colour_conversions() {
+        let source = "<p>a < b</span>";
+        let error = SlabkitError::Parse(Parser::new(source.to_string()).parse_nodes().unwrap_err());
+        let report = error.render(source, "page.html", true);
+
+        assert_eq!(
+            strip_colour(&report),
+            error.render(source, "page.html", false)
+        );
+        let html = colour_to_html(&report);
+        assert!(
+            html.starts_with(r#"<span class="slk-error">error</span>: <span class="slk-bold">"#)
+        );
+        assert!(
+            html.contains(r#"<span class="slk-note">1 |</span> &lt;p&gt;a &lt; b&lt;/span&gt;"#)
+        );
+        assert!(!html.contains('\x1b'));
+    }
+}
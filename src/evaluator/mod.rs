@@ -1,64 +1,392 @@
-use crate::parser::{HtmlElement, Node};
+use crate::data_path::{self, type_name};
+use crate::error::Location;
+use crate::file_ops;
+use crate::filters::Placeholder;
+use crate::html::{attribute_context, escape_in_context, text_context, Context, BLOCKED_URL};
+use crate::parser::{Attributes, HtmlElement, Node};
+use crate::template::Template;
+use indexmap::IndexMap;
 use serde_json::{json, Map, Value};
-use std::{collections::HashMap, f32::consts::E};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::LazyLock;
+
+// `!{{path}}!` and `!{path}!` placeholders, quoted keys of the path possibly containing braces.
+static PLACEHOLDER: LazyLock<regex::Regex> = LazyLock::new(|| {
+    let path = r#"(?:[^{}"'\n]|"(?:[^"\\]|\\.)*"|'(?:[^'\\]|\\.)*')+"#;
+    regex::Regex::new(&format!(r"!\{{\{{({path})\}}\}}!|!\{{({path})\}}!")).unwrap()
+});
+
+/*
+   What is done with a placeholder whose path isn't in the data, or whose value isn't a
+   string, number or boolean: it is kept as written, replaced by nothing, replaced by
+   nothing with a warning, or an error listing every unresolved placeholder.
+*/
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Undefined {
+    #[default]
+    Keep,
+    Empty,
+    Warn,
+    Error,
+}
+
+impl FromStr for Undefined {
+    type Err = String;
+
+    fn from_str(policy: &str) -> Result<Self, Self::Err> {
+        match policy {
+            "keep" => Ok(Undefined::Keep),
+            "empty" => Ok(Undefined::Empty),
+            "warn" => Ok(Undefined::Warn),
+            "error" => Ok(Undefined::Error),
+            _ => Err(format!(
+                "unknown policy '{}', expected keep, empty, warn or error",
+                policy
+            )),
+        }
+    }
+}
 
 pub struct Evaluator {
     json_template: Value,
+    undefined: Undefined,
+    // Files currently being evaluated, outermost first; the last one is the file
+    // `slk-include` paths are resolved against.
+    include_stack: Vec<PathBuf>,
+    // The directory includes must stay in, by default the one of the evaluated file, or the
+    // working directory.
+    root: Option<PathBuf>,
+    // Every file `slk-include`d so far, nested includes and missing files included.
+    included_files: Vec<PathBuf>,
+    // Problems that didn't stop the evaluation, such as blocked URLs.
+    warnings: Vec<String>,
+    // Unresolved placeholders, with the `Error` policy.
+    unresolved: Vec<String>,
 }
 
 impl Evaluator {
     pub fn new(json_tempalate: Value) -> Self {
         Self {
             json_template: json_tempalate,
+            undefined: Undefined::default(),
+            include_stack: Vec::new(),
+            root: None,
+            included_files: Vec::new(),
+            warnings: Vec::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    pub fn with_undefined(mut self, undefined: Undefined) -> Self {
+        self.undefined = undefined;
+        self
+    }
+
+    pub fn with_source_path(mut self, path: &Path) -> Self {
+        self.include_stack = vec![file_ops::normalize_path(path)];
+        self
+    }
+
+    pub fn with_root(mut self, root: &Path) -> Self {
+        self.root = Some(root.to_path_buf());
+        self
+    }
+
+    // Evaluator for a nested data scope that keeps the include context.
+    fn scoped(&self, json_template: Value) -> Evaluator {
+        Evaluator {
+            json_template,
+            undefined: self.undefined,
+            include_stack: self.include_stack.clone(),
+            root: self.root.clone(),
+            included_files: Vec::new(),
+            warnings: Vec::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    // Takes the included files, warnings and unresolved placeholders of an evaluator made
+    // by `scoped`.
+    fn merge(&mut self, mut evaluator: Evaluator) {
+        self.included_files.append(&mut evaluator.included_files);
+        self.warnings.append(&mut evaluator.warnings);
+        self.unresolved.append(&mut evaluator.unresolved);
+    }
+
+    // The files the evaluated nodes depend on through `slk-include`.
+    pub fn included_files(&self) -> &[PathBuf] {
+        &self.included_files
+    }
+
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /*
+       Evaluates the body of a template. Unlike `evaluate_children`, with the `Error`
+       policy it fails once the whole template is evaluated if any placeholder couldn't
+       be resolved, listing all of them.
+    */
+    pub fn evaluate_template(&mut self, nodes: Vec<Box<Node>>) -> Result<Vec<Box<Node>>, String> {
+        let nodes = self.evaluate_children(nodes)?;
+        if self.unresolved.is_empty() {
+            return Ok(nodes);
+        }
+        let mut unresolved: Vec<String> = Vec::new();
+        for placeholder in self.unresolved.drain(..) {
+            if !unresolved.contains(&placeholder) {
+                unresolved.push(placeholder);
+            }
         }
+        Err(format!(
+            "Slabkit error: {} unresolved placeholder{}:\n  {}",
+            unresolved.len(),
+            if unresolved.len() == 1 { "" } else { "s" },
+            unresolved.join("\n  ")
+        ))
     }
-    pub fn evaluate(&mut self, node: Node) -> Node {
-        let mut evaluated_node = match node {
-            Node::Text(text) => Node::Text(self.replace_placeholders(text + " ")),
+
+    pub fn evaluate(&mut self, node: Node) -> Result<Node, String> {
+        let evaluated_node = match node {
+            Node::Text(text, location) => Node::Text(
+                self.replace_placeholders_in(text, Context::Html, location),
+                location,
+            ),
             Node::Element(mut elt) => {
                 if elt.tag == "slk-datamap" {
                     return self.process_datamap(elt);
                 }
-                let mut new_attributes: HashMap<String, String> = HashMap::new();
+                if ["slk-if", "slk-else-if", "slk-else", "slk-include"].contains(&elt.tag.as_str())
+                {
+                    return self.process_root_fragment(elt);
+                }
+                let mut new_attributes = Attributes::new();
 
                 for attr in elt.attributes.iter_mut() {
                     let (key, value) = attr;
-                    *value = self.replace_placeholders(value.clone());
+                    let location = elt.attribute_locations.get(key).copied();
+                    *value = self.replace_placeholders_in(
+                        value.clone(),
+                        attribute_context(key),
+                        location,
+                    );
 
                     new_attributes.insert(key.clone(), value.clone());
                 }
 
-                if elt.children.len() == 0 {
-                    return Node::Element(HtmlElement {
+                if elt.children.is_empty() {
+                    return Ok(Node::Element(HtmlElement {
                         tag: elt.tag,
                         attributes: new_attributes,
                         children: Vec::new(),
-                    });
+                        attribute_locations: elt.attribute_locations,
+                    }));
                 }
-                let mut processed_children : Vec<Box<Node>> = Vec::new();
+                let children = std::mem::take(&mut elt.children);
+                elt.children = match text_context(&elt.tag) {
+                    Context::Html => self.evaluate_children(children)?,
+                    // The raw text of scripts and styles.
+                    context => children
+                        .into_iter()
+                        .map(|child| match *child {
+                            Node::Text(text, location) => Box::new(Node::Text(
+                                self.replace_placeholders_in(text, context, location),
+                                location,
+                            )),
+                            other => Box::new(other),
+                        })
+                        .collect(),
+                };
+                Node::Element(elt)
+            }
+        };
+        Ok(evaluated_node)
+    }
+
+    /*
+       Evaluates a list of sibling nodes.
+
+       `slk-if`, `slk-else-if` and `slk-else` siblings form a chain: only the children of
+       the first branch whose condition holds are kept, spliced in place of the chain.
+       `slk-include` elements are replaced by the evaluated content of the included file.
+    */
+    pub fn evaluate_children(
+        &mut self,
+        children: Vec<Box<Node>>,
+    ) -> Result<Vec<Box<Node>>, String> {
+        let mut processed_children: Vec<Box<Node>> = Vec::new();
+        // `None` outside of a chain, otherwise whether a branch of the chain was already taken.
+        let mut branch_taken: Option<bool> = None;
+
+        let children: Vec<Node> = children.into_iter().map(|child| *child).collect();
+        let mut index = 0;
+        while index < children.len() {
+            let child = children[index].clone();
+            index += 1;
 
-                for child in elt.children.drain(..) {
-                    processed_children
-                        .push(Box::new(Evaluator::new(self.json_template.clone()).evaluate(*child)));
+            let elt = match child {
+                Node::Element(elt) if elt.tag == "slk-include" => {
+                    branch_taken = None;
+                    processed_children.extend(self.process_include(elt)?);
+                    continue;
                 }
-                elt.children = processed_children;
                 Node::Element(elt)
+                    if elt.tag == "slk-if"
+                        || (branch_taken.is_some()
+                            && (elt.tag == "slk-else-if" || elt.tag == "slk-else")) =>
+                {
+                    elt
+                }
+                // Whitespace between the branches of a chain belongs to the chain.
+                Node::Text(text, _)
+                    if branch_taken.is_some()
+                        && is_blank(&text)
+                        && children[index..]
+                            .iter()
+                            .find(
+                                |sibling| !matches!(sibling, Node::Text(text, _) if is_blank(text)),
+                            )
+                            .is_some_and(|sibling| {
+                                matches!(sibling, Node::Element(elt)
+                                    if elt.tag == "slk-else-if" || elt.tag == "slk-else")
+                            }) =>
+                {
+                    continue;
+                }
+                Node::Element(elt) if elt.tag == "slk-else-if" || elt.tag == "slk-else" => {
+                    return Err(format!(
+                        "Slabkit error: {} without a preceding slk-if",
+                        elt.tag
+                    ));
+                }
+                other => {
+                    branch_taken = None;
+                    processed_children.push(Box::new(self.evaluate(other)?));
+                    continue;
+                }
+            };
+
+            if elt.tag == "slk-if" {
+                branch_taken = Some(false);
             }
-        };
-        evaluated_node
+            if branch_taken == Some(false) && (elt.tag == "slk-else" || self.is_condition_met(&elt))
+            {
+                branch_taken = Some(true);
+                processed_children.extend(self.evaluate_children(elt.children)?);
+            }
+            if elt.tag == "slk-else" {
+                branch_taken = None;
+            }
+        }
+        Ok(processed_children)
     }
 
-    pub fn get_literal_from_template(&mut self, path: String) -> Result<String, String> {
-        let json_content = &self.json_template;
+    /*
+       Evaluates an element that expands to a list of nodes (`slk-if`, `slk-include`)
+       when it is not nested in a parent that the nodes can be spliced into.
+    */
+    pub fn process_root_fragment(&mut self, elt: HtmlElement) -> Result<Node, String> {
+        let mut processed_children = self.evaluate_children(vec![Box::new(Node::Element(elt))])?;
+        Ok(match processed_children.len() {
+            0 => Node::Text(String::new(), None),
+            1 => *processed_children.remove(0),
+            _ => Node::Element(HtmlElement {
+                tag: "div".to_string(),
+                attributes: Attributes::new(),
+                children: processed_children,
+                attribute_locations: IndexMap::new(),
+            }),
+        })
+    }
+
+    /*
+       Loads the file referenced by an `slk-include` element relative to the including
+       template, which must be inside the root directory, evaluates it with the current
+       data (or the `data` sub-scope) and returns the resulting nodes.
+    */
+    pub fn process_include(&mut self, elt: HtmlElement) -> Result<Vec<Box<Node>>, String> {
+        let src = match elt.attributes.get("src") {
+            Some(src) => src.trim().to_string(),
+            None => return Err("Slabkit error: slk-include requires a src attribute".to_string()),
+        };
+        let root = match &self.root {
+            Some(root) => root.clone(),
+            None => self
+                .include_stack
+                .first()
+                .and_then(|file| file.parent())
+                .map_or_else(|| PathBuf::from("."), Path::to_path_buf),
+        };
+        let path = file_ops::resolve_include_path(&root, self.include_stack.last(), &src)
+            .map_err(|error| format!("Slabkit error: couldn't include '{}': {}", src, error))?;
 
-        let mut value = &self.json_template;
-        for p in path.split('.').into_iter() {
-            match value.get(p) {
-                Some(v) => value = v,
-                None => return Err(format!("Path '{}' not found in JSON template.", path)),
+        if let Some(index) = self.include_stack.iter().position(|p| *p == path) {
+            let mut cycle: Vec<String> = self.include_stack[index..]
+                .iter()
+                .map(|p| self.display_path(p))
+                .collect();
+            cycle.push(self.display_path(&path));
+            return Err(format!(
+                "Slabkit error: include cycle detected: {}",
+                cycle.join(" -> ")
+            ));
+        }
+
+        self.included_files.push(path.clone());
+        let source = file_ops::read_file(&path).map_err(|error| {
+            format!(
+                "Slabkit error: couldn't include '{}' ({}): {}",
+                src,
+                self.display_path(&path),
+                error
+            )
+        })?;
+
+        let data = match elt.attributes.get("data") {
+            Some(data_path) => {
+                let data_path = self.unwrap_placeholders(data_path.clone());
+                self.get_value_from_template(data_path)
+                    .map_err(|error| format!("Slabkit error: slk-include '{}': {}", src, error))?
+                    .clone()
             }
+            None => self.json_template.clone(),
+        };
+
+        let template = Template::parse(source.clone()).map_err(|error| {
+            let name = self.display_path(&path);
+            format!(
+                "Slabkit error: in '{}':\n{}",
+                name,
+                error.render(&source, &name, false)
+            )
+        })?;
+
+        let mut evaluator = self.scoped(data);
+        evaluator.include_stack.push(path);
+        let result = evaluator.evaluate_children(template.body());
+        self.merge(evaluator);
+        result
+    }
+
+    pub fn is_condition_met(&mut self, elt: &HtmlElement) -> bool {
+        let path = match elt.attributes.get("condition") {
+            Some(condition) => self.unwrap_placeholders(condition.clone()),
+            None => return false,
+        };
+        match self.get_value_from_template(path) {
+            Ok(value) => is_truthy(value),
+            Err(_) => false,
         }
-        match value {
+    }
+
+    // The value at `path` in the data; see `DataPath` for the path syntax.
+    pub fn get_value_from_template(&mut self, path: String) -> Result<&Value, String> {
+        data_path::lookup(&self.json_template, &path)
+    }
+
+    pub fn get_literal_from_template(&mut self, path: String) -> Result<String, String> {
+        match self.get_value_from_template(path)? {
             Value::String(a) => Ok(a.to_string()),
             Value::Bool(a) => Ok(a.to_string()),
             Value::Number(a) => Ok(a.to_string()),
@@ -66,14 +394,7 @@ impl Evaluator {
         }
     }
     pub fn get_array_from_template(&mut self, path: String) -> Result<&Vec<Value>, String> {
-        let mut value = &self.json_template;
-        for p in path.split('.').into_iter() {
-            match value.get(p) {
-                Some(v) => value = v,
-                None => return Err(format!("Path '{}' not found in JSON template.", path)),
-            }
-        }
-        match value {
+        match self.get_value_from_template(path)? {
             Value::Array(a) => Ok(a),
             _ => Err("Value not found".to_string()),
         }
@@ -83,91 +404,615 @@ impl Evaluator {
         &mut self,
         path: String,
     ) -> Result<&Map<String, Value>, String> {
-        let json_content = &self.json_template;
-
-        let mut value = &self.json_template;
-
-        for p in path.split('.').into_iter() {
-            match value.get(p) {
-                Some(v) => value = v,
-                None => return Err(format!("Path '{}' not found in JSON template.", path)),
-            }
-        }
-
-        match value {
+        match self.get_value_from_template(path)? {
             Value::Object(a) => Ok(a),
             _ => Err("Value not found".to_string()),
         }
     }
 
     pub fn replace_placeholders(&mut self, input: String) -> String {
-        let mut result = input.to_string();
-        let placeholder_pattern = regex::Regex::new(r"!\{([a-zA-Z0-9_.]+)\}!").unwrap();
+        self.replace_placeholders_in(input, Context::Html, None)
+    }
 
+    /*
+       Replaces `!{path}!` placeholders with their value, escaped for the `context` of
+       `input` so data can't inject markup or code, and `!{{path}}!` placeholders with
+       their value as is, for trusted HTML. Values go through the placeholder's filters
+       first, as in `!{price | number:2}!`. Unsafe URLs are replaced by `BLOCKED_URL` with
+       a warning. Placeholders whose path isn't in the data are handled by the `Undefined`
+       policy, and reported at their place in the template when `location`, where `input`
+       starts, is known.
+    */
+    pub fn replace_placeholders_in(
+        &mut self,
+        input: String,
+        context: Context,
+        location: Option<Location>,
+    ) -> String {
         // Replace each placeholder with the corresponding value from the JSON
-        for caps in placeholder_pattern.captures_iter(&input) {
-            if let Some(placeholder) = caps.get(1) {
-                let key_path = placeholder.as_str();
+        PLACEHOLDER
+            .replace_all(&input, |caps: &regex::Captures| {
+                let (content, raw) = match caps.get(1) {
+                    Some(content) => (content.as_str(), true),
+                    None => (&caps[2], false),
+                };
+                let start = caps.get(0).map_or(0, |placeholder| placeholder.start());
+                let location = location.map(|location| location.after(&input[..start]));
+                let placeholder = match Placeholder::parse(content) {
+                    Ok(placeholder) => placeholder,
+                    Err(reason) => return self.unresolved(&caps[0], location, reason),
+                };
+                let value = self
+                    .get_value_from_template(placeholder.path.clone())
+                    .cloned();
+                let value = match placeholder.apply(value) {
+                    Ok(value @ (Value::String(_) | Value::Number(_) | Value::Bool(_))) => value,
+                    Ok(value) => {
+                        let reason = format!(
+                            "'{}' is {}, not a string, number or boolean",
+                            placeholder.path,
+                            type_name(&value)
+                        );
+                        return self.unresolved(&caps[0], location, reason);
+                    }
+                    Err(reason) => return self.unresolved(&caps[0], location, reason),
+                };
+                if raw || placeholder.raw {
+                    return match value {
+                        Value::String(text) => text,
+                        value => value.to_string(),
+                    };
+                }
+                let before = &input[..start];
+                escape_in_context(&value, context, before).unwrap_or_else(|warning| {
+                    self.warnings.push(format!("{} from {}", warning, &caps[0]));
+                    BLOCKED_URL.to_string()
+                })
+            })
+            .to_string()
+    }
 
-                if let Ok(value) = self.get_literal_from_template(key_path.to_string()) {
-                    result = result.replace(&format!("!{{{}}}!", key_path), &value);
+    // What an unresolved placeholder is replaced with, depending on the `Undefined` policy.
+    fn unresolved(
+        &mut self,
+        placeholder: &str,
+        location: Option<Location>,
+        reason: String,
+    ) -> String {
+        let describe = |evaluator: &Evaluator| match location {
+            Some(location) => format!(
+                "{} at {}: {}",
+                placeholder,
+                evaluator.describe_location(location),
+                reason
+            ),
+            None => format!("{}: {}", placeholder, reason),
+        };
+        match self.undefined {
+            Undefined::Keep => placeholder.to_string(),
+            Undefined::Empty => String::new(),
+            Undefined::Warn => {
+                let warning = format!("unresolved placeholder {}", describe(self));
+                if !self.warnings.contains(&warning) {
+                    self.warnings.push(warning);
                 }
+                String::new()
+            }
+            Undefined::Error => {
+                self.unresolved.push(describe(self));
+                placeholder.to_string()
             }
         }
-        result
     }
 
-    pub fn unwrap_placeholders(&mut self, text: String) -> String {
-        let mut result = text.to_string();
-        result = result.replace('{', "");
-        result = result.replace('}', "");
-        result = result.replace('!', "");
-        result = result.replace('!', "");
-        result = result.replace(' ', "");
+    // `file:line:column` of a location in the file being evaluated, or `line:column`.
+    fn describe_location(&self, location: Location) -> String {
+        let position = format!("{}:{}", location.line, location.column);
+        match self.include_stack.last() {
+            Some(file) => format!("{}:{}", self.display_path(file), position),
+            None => position,
+        }
+    }
 
-        result
+    // A path as shown in errors: relative to the root when one was given, since the
+    // errors may be sent to whoever picked the template, or to the working directory.
+    fn display_path(&self, path: &Path) -> String {
+        let base = match &self.root {
+            Some(root) => std::path::absolute(root).ok(),
+            None => std::env::current_dir().ok(),
+        };
+        std::path::absolute(path)
+            .ok()
+            .zip(base)
+            .and_then(|(path, base)| path.strip_prefix(base).ok().map(Path::to_path_buf))
+            .unwrap_or_else(|| path.to_path_buf())
+            .display()
+            .to_string()
     }
-    pub fn process_datamap(&mut self, mut elt: HtmlElement) -> Node {
 
-        if (elt.children.len() > 1) {
-            panic!("Error: slk-datamap can only have one child element");
+    // The path of an attribute such as `condition="!{section.title}!"`.
+    pub fn unwrap_placeholders(&mut self, text: String) -> String {
+        let text = text.trim();
+        text.strip_prefix("!{")
+            .and_then(|text| text.strip_suffix("}!"))
+            .unwrap_or(text)
+            .trim()
+            .to_string()
+    }
+    pub fn process_datamap(&mut self, mut elt: HtmlElement) -> Result<Node, String> {
+        let element_children = elt
+            .children
+            .iter()
+            .filter(|child| matches!(***child, Node::Element(_)))
+            .count();
+        if element_children > 1 {
+            return Err("Slabkit error: slk-datamap can only have one child element".to_string());
         }
-        let data_path = match elt.attributes.remove("data") {
+        let data_path = match elt.attributes.shift_remove("data") {
             Some(path) => self.unwrap_placeholders(path.clone()),
-            None => return Node::Element(elt),
+            None => return Ok(Node::Element(elt)),
         };
 
-        let selector = match elt.attributes.remove("selector") {
+        let selector = match elt.attributes.shift_remove("selector") {
             Some(selector) => self.unwrap_placeholders(selector.clone()),
-            None => return Node::Element(elt),
+            None => return Ok(Node::Element(elt)),
         };
         // Clone the template to avoid borrowing issues
         let data_array = match self.get_array_from_template(data_path) {
             Ok(array) => array.clone(),
-            Err(_) => return Node::Element(elt),
+            Err(_) => return Ok(Node::Element(elt)),
         };
- 
 
         let mut processed_children: Vec<Box<Node>> = Vec::new();
 
-        let first_child = elt.children.first();
-        if first_child.is_none() {
-            return Node::Element(elt);
+        if elt.children.is_empty() {
+            return Ok(Node::Element(elt));
         }
 
+        // The child is repeated together with the whitespace around it.
         for item in data_array.into_boxed_slice().iter() {
             let item_json: Value = json!({ selector.clone(): item });
-            if let Some(child) = first_child {
-                processed_children.push(Box::new(Evaluator::new(item_json).evaluate(*child.clone())));
-            } else {
-                return Node::Element(elt);
-            }
+            let mut evaluator = self.scoped(item_json);
+            let children = evaluator.evaluate_children(elt.children.clone());
+            self.merge(evaluator);
+            processed_children.extend(children?);
         }
 
-        Node::Element(HtmlElement {
+        Ok(Node::Element(HtmlElement {
             tag: "div".to_string(),
             attributes: elt.attributes.clone(),
             children: processed_children,
-        })
+            attribute_locations: IndexMap::new(),
+        }))
+    }
+}
+
+fn is_blank(text: &str) -> bool {
+    text.trim().is_empty()
+}
+
+/*
+   @param value: &Value
+   @return bool
+
+   Truthiness used by `slk-if` / `slk-else-if` conditions: null, false, 0, empty strings,
+   empty arrays and empty objects are falsy, everything else is truthy.
+*/
+pub fn is_truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64().is_some_and(|n| n != 0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+#[cfg(test)]
+mod evaluator_tests {
+    use serde_json::json;
+
+    use crate::{
+        evaluator::{Evaluator, Undefined},
+        parser::Parser,
+    };
+
+    fn render(template: &str, data: serde_json::Value) -> String {
+        let node = Parser::new(template.to_string()).parse().unwrap();
+        Evaluator::new(data).evaluate(node).unwrap().node_to_html()
+    }
+
+    #[test]
+    fn test_if_renders_children_when_truthy() {
+        let template = r#"<div><slk-if condition="!{show}!"><p>Shown</p></slk-if></div>"#;
+        assert_eq!(
+            render(template, json!({ "show": true })),
+            "<div><p>Shown</p></div>"
+        );
+        assert_eq!(render(template, json!({ "show": false })), "<div></div>");
+    }
+
+    #[test]
+    fn test_if_else_if_else_chain() {
+        let template = "<div>
+  <slk-if condition=\"!{a}!\"><p>A</p></slk-if>
+  <slk-else-if condition=\"!{b}!\"><p>B</p></slk-else-if>
+  <slk-else><p>C</p></slk-else>
+</div>";
+        assert_eq!(
+            render(template, json!({ "a": "x", "b": [1] })),
+            "<div>\n  <p>A</p>\n</div>"
+        );
+        assert_eq!(
+            render(template, json!({ "a": "", "b": [1] })),
+            "<div>\n  <p>B</p>\n</div>"
+        );
+        assert_eq!(
+            render(template, json!({ "a": null, "b": [] })),
+            "<div>\n  <p>C</p>\n</div>"
+        );
+        assert_eq!(render(template, json!({})), "<div>\n  <p>C</p>\n</div>");
+    }
+
+    #[test]
+    fn test_else_without_if() {
+        let evaluate = |template: &str| {
+            let node = Parser::new(template.to_string()).parse().unwrap();
+            Evaluator::new(json!({}))
+                .evaluate(node)
+                .map(|node| node.node_to_html())
+        };
+        assert_eq!(
+            evaluate(r#"<div><slk-else-if condition="true"><p>B</p></slk-else-if></div>"#),
+            Err("Slabkit error: slk-else-if without a preceding slk-if".to_string())
+        );
+        assert_eq!(
+            evaluate("<div><p>A</p><slk-else><p>C</p></slk-else></div>"),
+            Err("Slabkit error: slk-else without a preceding slk-if".to_string())
+        );
+        assert_eq!(
+            evaluate("<slk-else><p>C</p></slk-else>"),
+            Err("Slabkit error: slk-else without a preceding slk-if".to_string())
+        );
+    }
+
+    #[test]
+    fn test_placeholder_paths() {
+        let data = json!({
+            "people": [{ "name": "Ada" }, { "name": "Grace" }],
+            "labels": { "first name": "First", "a}!b": "Braces" }
+        });
+        assert_eq!(
+            render(
+                r#"<p title="!{people.0.name}!">!{people[1].name}! !{people[-1].name}! !{labels["first name"]}!</p>"#,
+                data.clone()
+            ),
+            r#"<p title="Ada">Grace Grace First</p>"#
+        );
+        assert_eq!(
+            render(
+                r#"<p>!{labels['a}!b']}! !{people[5].name}! !{ !{people.-2.name}!</p>"#,
+                data.clone()
+            ),
+            "<p>Braces !{people[5].name}! !{ Ada</p>"
+        );
+        assert_eq!(
+            render(
+                r#"<div><slk-if condition="!{ people[-1] }!"><p>Last</p></slk-if></div>"#,
+                data
+            ),
+            "<div><p>Last</p></div>"
+        );
+    }
+
+    #[test]
+    fn test_placeholders_are_escaped() {
+        let data = json!({
+            "name": "<script>alert('Tom & Jerry')</script>",
+            "quote": "\"hi\" & bye",
+            "body": "<b>Bold</b>"
+        });
+        assert_eq!(
+            render(
+                r#"<p title="!{quote}!">!{name}! !{{body}}! !{{ missing }}!</p>"#,
+                data
+            ),
+            r#"<p title="&quot;hi&quot; &amp; bye">&lt;script&gt;alert(&#39;Tom &amp; Jerry&#39;)&lt;/script&gt; <b>Bold</b> !{{ missing }}!</p>"#
+        );
+    }
+
+    #[test]
+    fn test_placeholders_are_escaped_for_their_context() {
+        let node = Parser::new(
+            r#"<div><a href="!{link}!" title="!{link}!">Go</a><img src="/img?name=!{name}!" style="font-family:!{font}!;"><script>var name = "!{name}!", count = !{count}!;</script></div>"#
+                .to_string(),
+        )
+        .parse()
+        .unwrap();
+        let mut evaluator = Evaluator::new(json!({
+            "link": "javascript:alert(1)",
+            "name": "A&B \"</script>",
+            "font": "x;}",
+            "count": 2
+        }));
+        let html = evaluator.evaluate(node).unwrap().node_to_html();
+
+        assert_eq!(
+            html,
+            r#"<div><a href="about:invalid#slabkit-blocked" title="javascript:alert(1)">Go</a><img src="/img?name=A%26B%20%22%3C%2Fscript%3E" style="font-family:x\3b \7d ;"><script>var name = "A\u0026B \u0022\u003c/script\u003e", count = 2;</script></div>"#
+        );
+        assert_eq!(
+            evaluator.warnings(),
+            &["blocked the unsafe URL 'javascript:alert(1)' from !{link}!".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_script_values_stay_values() {
+        let data = json!({ "count": "1; alert(document.cookie)", "page": "<script>x()</script>" });
+        assert_eq!(
+            render(
+                "<script>\n// Don't touch\nvar count = !{count}!;\n</script>",
+                data.clone()
+            ),
+            "<script>\n// Don't touch\nvar count = \"1; alert(document.cookie)\";\n</script>"
+        );
+        assert_eq!(
+            render("<script>var m = `x ${!{count}!}`;</script>", data.clone()),
+            "<script>var m = `x ${\"1; alert(document.cookie)\"}`;</script>"
+        );
+        assert_eq!(
+            render(r#"<iframe srcdoc="<p>!{page}!</p>"></iframe>"#, data),
+            r#"<iframe srcdoc="<p>&amp;lt;script&amp;gt;x()&amp;lt;/script&amp;gt;</p>"></iframe>"#
+        );
+    }
+
+    #[test]
+    fn test_undefined_policies() {
+        let evaluate = |undefined: Undefined| {
+            let nodes = Parser::new(r#"<p title="!{a}!">!{title}! !{a}! !{list}!</p>"#.to_string())
+                .parse_nodes()
+                .unwrap();
+            let mut evaluator =
+                Evaluator::new(json!({ "title": "T", "list": [1] })).with_undefined(undefined);
+            let result = evaluator.evaluate_template(nodes.into_iter().map(Box::new).collect());
+            (
+                result.map(|nodes| nodes[0].node_to_html()),
+                evaluator.warnings().to_vec(),
+            )
+        };
+
+        assert_eq!(
+            evaluate(Undefined::Keep),
+            (
+                Ok(r#"<p title="!{a}!">T !{a}! !{list}!</p>"#.to_string()),
+                vec![]
+            )
+        );
+        assert_eq!(
+            evaluate(Undefined::Empty),
+            (Ok("<p title>T  </p>".to_string()), vec![])
+        );
+        let (html, warnings) = evaluate(Undefined::Warn);
+        assert_eq!(html, Ok("<p title>T  </p>".to_string()));
+        assert_eq!(
+            warnings,
+            vec![
+                "unresolved placeholder !{a}! at 1:11: Path 'a' not found in JSON template: no key 'a' in the data",
+                "unresolved placeholder !{a}! at 1:28: Path 'a' not found in JSON template: no key 'a' in the data",
+                "unresolved placeholder !{list}! at 1:34: 'list' is array, not a string, number or boolean",
+            ]
+        );
+        assert_eq!(
+            evaluate(Undefined::Error).0,
+            Err("Slabkit error: 3 unresolved placeholders:
+  !{a}! at 1:11: Path 'a' not found in JSON template: no key 'a' in the data
+  !{a}! at 1:28: Path 'a' not found in JSON template: no key 'a' in the data
+  !{list}! at 1:34: 'list' is array, not a string, number or boolean"
+                .to_string())
+        );
+    }
+
+    #[test]
+    fn test_unresolved_placeholder_locations() {
+        let dir = write_partials(
+            "slabkit_undefined_locations",
+            &[(
+                "partials/card.html",
+                "<div>\n  <h2>!{name}!</h2>\n  <p>!{missing}!</p>\n</div>",
+            )],
+        );
+        // Each place of the same placeholder is reported, in the file it is in.
+        let source = "<section>\n  <p data-x='!{missing}!'>!{title}!</p>\n  <slk-include src=\"partials/card.html\"></slk-include>\n  <p>\n    !{title}! !{missing}!</p>\n</section>";
+        let nodes = Parser::new(source.to_string()).parse_nodes().unwrap();
+        let result = Evaluator::new(json!({ "title": "T", "name": "N" }))
+            .with_source_path(&dir.join("page.html"))
+            .with_undefined(Undefined::Error)
+            .evaluate_template(nodes.into_iter().map(Box::new).collect());
+
+        let page = dir.join("page.html");
+        let card = dir.join("partials/card.html").canonicalize().unwrap();
+        assert_eq!(
+            result.map(|_| ()),
+            Err(format!(
+                "Slabkit error: 3 unresolved placeholders:
+  !{{missing}}! at {page}:2:14: Path 'missing' not found in JSON template: no key 'missing' in the data
+  !{{missing}}! at {card}:3:6: Path 'missing' not found in JSON template: no key 'missing' in the data
+  !{{missing}}! at {page}:5:15: Path 'missing' not found in JSON template: no key 'missing' in the data",
+                page = page.display(),
+                card = card.display()
+            ))
+        );
+    }
+
+    #[test]
+    fn test_placeholder_filters() {
+        let data = json!({
+            "person": { "name": "ada" },
+            "price": 4.5,
+            "tags": ["rust", "html"],
+            "bio": "<b>Bold</b> and more"
+        });
+        assert_eq!(
+            render(
+                r#"<p title='!{title | default:"Untitled"}!'>!{person.name | capitalize}! !{price | number:2}! !{tags | join:" / " | upper}!</p>"#,
+                data.clone()
+            ),
+            r#"<p title="Untitled">Ada 4.50 RUST / HTML</p>"#
+        );
+        assert_eq!(
+            render(
+                "<p>!{bio | truncate:11}! !{bio | truncate:11 | raw}! !{price | shout}!</p>",
+                data
+            ),
+            "<p>&lt;b&gt;Bold&lt;/b&gt;... <b>Bold</b>... !{price | shout}!</p>"
+        );
+    }
+
+    fn write_partials(dir: &str, files: &[(&str, &str)]) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(dir);
+        std::fs::create_dir_all(dir.join("partials")).unwrap();
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_include_with_data_scope() {
+        let dir = write_partials(
+            "slabkit_include_scope",
+            &[
+                (
+                    "page.html",
+                    r#"<div><slk-include src="partials/card.html" data="!{person}!"></slk-include></div>"#,
+                ),
+                (
+                    "partials/card.html",
+                    "<slk-section><h1>!{name}!</h1><p>!{role}!</p></slk-section>",
+                ),
+            ],
+        );
+        let page = std::fs::read_to_string(dir.join("page.html")).unwrap();
+        let node = Parser::new(page).parse().unwrap();
+        let mut evaluator =
+            Evaluator::new(json!({ "person": { "name": "Regis", "role": "Author" } }))
+                .with_source_path(&dir.join("page.html"));
+        let html = evaluator.evaluate(node).unwrap().node_to_html();
+        assert_eq!(html, "<div><h1>Regis</h1><p>Author</p></div>");
+        assert_eq!(
+            evaluator.included_files(),
+            &[dir.join("partials/card.html").canonicalize().unwrap()]
+        );
+    }
+
+    #[test]
+    fn test_include_missing_file_and_cycle() {
+        let dir = write_partials(
+            "slabkit_include_errors",
+            &[
+                (
+                    "missing.html",
+                    r#"<div><slk-include src="partials/nope.html"></slk-include></div>"#,
+                ),
+                (
+                    "a.html",
+                    r#"<div><slk-include src="b.html"></slk-include></div>"#,
+                ),
+                (
+                    "b.html",
+                    r#"<p><slk-include src="a.html"></slk-include></p>"#,
+                ),
+            ],
+        );
+        for (file, expected) in [
+            ("missing.html", "couldn't include 'partials/nope.html'"),
+            ("a.html", "include cycle detected"),
+        ] {
+            let source = std::fs::read_to_string(dir.join(file)).unwrap();
+            let node = Parser::new(source).parse().unwrap();
+            let error = Evaluator::new(json!({}))
+                .with_source_path(&dir.join(file))
+                .evaluate(node)
+                .unwrap_err();
+            assert!(error.contains(expected), "{}", error);
+        }
+    }
+
+    #[test]
+    fn test_include_outside_root() {
+        let dir = write_partials(
+            "slabkit_include_root",
+            &[
+                (
+                    "partials/parent.html",
+                    r#"<div><slk-include src="../secret.txt"></slk-include></div>"#,
+                ),
+                ("secret.txt", "secret"),
+            ],
+        );
+        let absolute = dir.join("secret.txt").canonicalize().unwrap();
+        for src in [
+            "../secret.txt",
+            "../../secret.txt",
+            absolute.to_str().unwrap(),
+        ] {
+            let template = format!(r#"<div><slk-include src="{}"></slk-include></div>"#, src);
+            let node = Parser::new(template).parse().unwrap();
+            let error = Evaluator::new(json!({}))
+                .with_source_path(&dir.join("partials/page.html"))
+                .evaluate(node)
+                .unwrap_err();
+            assert!(
+                error.contains(&format!("couldn't include '{}'", src)),
+                "{}",
+                error
+            );
+            // Only the `src` as written names a path.
+            let error = error.replace(src, "");
+            assert!(!error.contains(dir.to_str().unwrap()), "{}", error);
+        }
+
+        // With a root, paths in errors are relative to it.
+        let template = r#"<div><slk-include src="missing.html"></slk-include></div>"#;
+        let node = Parser::new(template.to_string()).parse().unwrap();
+        let error = Evaluator::new(json!({}))
+            .with_source_path(&dir.join("partials/page.html"))
+            .with_root(&dir)
+            .evaluate(node)
+            .unwrap_err();
+        assert!(
+            error.starts_with(
+                "Slabkit error: couldn't include 'missing.html' (partials/missing.html): "
+            ),
+            "{}",
+            error
+        );
+
+        // `..` is fine as long as it stays in the root.
+        let source = std::fs::read_to_string(dir.join("partials/parent.html")).unwrap();
+        let node = Parser::new(source).parse().unwrap();
+        let html = Evaluator::new(json!({}))
+            .with_source_path(&dir.join("partials/parent.html"))
+            .with_root(&dir)
+            .evaluate(node)
+            .unwrap()
+            .node_to_html();
+        assert_eq!(html, "<div>secret</div>");
+    }
+
+    #[test]
+    fn test_truthiness() {
+        use super::is_truthy;
+        assert!(!is_truthy(&json!(null)));
+        assert!(!is_truthy(&json!("")));
+        assert!(!is_truthy(&json!([])));
+        assert!(!is_truthy(&json!(false)));
+        assert!(!is_truthy(&json!(0)));
+        assert!(is_truthy(&json!("a")));
+        assert!(is_truthy(&json!([null])));
+        assert!(is_truthy(&json!(true)));
+        assert!(is_truthy(&json!({ "a": 1 })));
     }
 }
@@ -1,9 +1,9 @@
-use crate::{logger::Logger, parser::Parser};
-use regex::Regex;
+use crate::logger::Logger;
 use serde_json::Value;
 use std::{
-    fs::File,
+    fs::{self, File},
     io::{BufReader, Read},
+    path::{Component, Path, PathBuf},
 };
 
 /*
@@ -36,24 +36,109 @@ pub fn get_file(path: &str) -> String {
     }
 }
 
+/*
+   @param path: &Path
+   @return Result<String, String>
+
+   Same as `get_file` but reports a missing or unreadable file to the caller.
+*/
+pub fn read_file(path: &Path) -> Result<String, String> {
+    let file = File::open(path).map_err(|error| format!("{}", error))?;
+    let mut contents = String::new();
+    BufReader::new(file)
+        .read_to_string(&mut contents)
+        .map_err(|error| format!("{}", error))?;
+    Ok(contents)
+}
+
+/*
+   @param path: &Path
+   @return PathBuf
 
+   Canonical form of the path when it exists, used to compare files for include cycles.
+*/
+pub fn normalize_path(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
 
-pub fn get_slk_section(template: &str, section: &str) -> Result<String, String> {
-    // Construct regex dynamically with dot-all flag to handle multiline
-    let matching_string = format!(r"(?s)<{section}>(.*?)</{section}>", section = section);
-    println!("Matching string: {:?}", matching_string);
+/*
+   @param root: &Path
+   @param including_file: Option<&PathBuf>
+   @param src: &str
+   @return Result<PathBuf, String>
 
-    let re = Regex::new(&matching_string).unwrap();
+   Resolves the `src` of an `slk-include` relative to the directory of the including file,
+   or to `root` when the template has no known location. Absolute paths, and paths that
+   lead outside of `root` through `..` or symbolic links, are refused.
+*/
+pub fn resolve_include_path(
+    root: &Path,
+    including_file: Option<&PathBuf>,
+    src: &str,
+) -> Result<PathBuf, String> {
+    if Path::new(src).has_root() {
+        return Err("absolute paths can't be included".to_string());
+    }
+    let base = including_file
+        .and_then(|file| file.parent())
+        .unwrap_or(root);
+    let path = normalize_path(&clean_path(&base.join(src)));
+    let root = normalize_path(&clean_path(root));
+    if !path.starts_with(&root) {
+        return Err("the path leads outside of the template root".to_string());
+    }
+    Ok(path)
+}
 
-    // Check for captures and return matched content
-    if let Some(cap) = re.captures(template) {
-        if let Some(matched_section) = cap.get(1) {
-            return Ok(matched_section.as_str().to_string())
+// The absolute form of the path with `.` and `..` resolved, whether the path exists or not.
+fn clean_path(path: &Path) -> PathBuf {
+    let mut clean = PathBuf::new();
+    for component in std::path::absolute(path)
+        .unwrap_or_else(|_| path.to_path_buf())
+        .components()
+    {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                clean.pop();
+            }
+            component => clean.push(component),
         }
     }
+    clean
+}
+
+/*
+   @param dir: &Path
+   @return Vec<PathBuf>
+
+   Every file under `dir`, relative to it and sorted. Hidden entries (`.git`, `.lepper`)
+   and `target` directories are skipped.
+*/
+pub fn list_files(dir: &Path) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    collect_files(dir, Path::new(""), &mut files);
+    files.sort();
+    files
+}
 
-    println!("No matching section found for <{}>", section);
-    Err(format!("Slabkit error: couldn't resolve section {} from the provided template", template))
+fn collect_files(dir: &Path, relative: &Path, files: &mut Vec<PathBuf>) {
+    let entries = match fs::read_dir(dir.join(relative)) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        let name = entry.file_name();
+        if name.to_string_lossy().starts_with('.') || name == "target" {
+            continue;
+        }
+        let path = relative.join(&name);
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => collect_files(dir, &path, files),
+            Ok(_) => files.push(path),
+            Err(_) => {}
+        }
+    }
 }
 
 pub fn get_json_value_from_template(json: String) -> Value {
@@ -65,4 +150,4 @@ pub fn get_json_value_from_template(json: String) -> Value {
             Value::Null
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,573 @@
+use serde_json::{Number, Value};
+
+use crate::data_path::type_name;
+
+// Every filter placeholders can use.
+const FILTERS: [&str; 12] = [
+    "upper",
+    "lower",
+    "capitalize",
+    "trim",
+    "length",
+    "number",
+    "truncate",
+    "default",
+    "join",
+    "date",
+    "raw",
+    "json",
+];
+
+// The most decimals `number` formats, so a template can't make it allocate without bound.
+const MAX_DECIMALS: usize = 20;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+// A filter of a placeholder with its arguments, such as `truncate:120,"…"`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct Filter {
+    pub name: String,
+    pub args: Vec<Value>,
+}
+
+/*
+   The content of a placeholder: a data path followed by filters, applied left to right,
+   as in `!{person.name | trim | upper}!`. Filter arguments follow a `:`, separated by
+   commas; they are numbers, or strings, quoted when they contain spaces, commas or `|`.
+   `raw` inserts the value without escaping, like `!{{path}}!`.
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct Placeholder {
+    pub path: String,
+    pub filters: Vec<Filter>,
+    pub raw: bool,
+}
+
+impl Placeholder {
+    /*
+       @param text: &str
+       @return Result<Placeholder, String>
+
+       Parses the text between `!{` and `}!`. Unknown filters are errors.
+    */
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut parts = split_unquoted(text, '|').into_iter();
+        let path = parts.next().unwrap_or_default().trim().to_string();
+        let mut placeholder = Placeholder {
+            path,
+            filters: Vec::new(),
+            raw: false,
+        };
+
+        for part in parts {
+            let (name, args) = match part.split_once(':') {
+                Some((name, args)) => (name.trim(), split_unquoted(args, ',')),
+                None => (part.trim(), Vec::new()),
+            };
+            if !FILTERS.contains(&name) {
+                return Err(format!(
+                    "unknown filter '{}', expected one of {}",
+                    name,
+                    FILTERS.join(", ")
+                ));
+            }
+            if name == "raw" {
+                placeholder.raw = true;
+                continue;
+            }
+            placeholder.filters.push(Filter {
+                name: name.to_string(),
+                args: args.iter().map(|arg| parse_arg(arg)).collect(),
+            });
+        }
+        Ok(placeholder)
+    }
+
+    /*
+       @param value: Result<Value, String>
+       @return Result<Value, String>
+
+       Runs the filters on the value at the path, or on the error of a path that isn't in
+       the data, which only `default` recovers from.
+    */
+    pub fn apply(&self, value: Result<Value, String>) -> Result<Value, String> {
+        self.filters
+            .iter()
+            .fold(value, |value, filter| match filter.name.as_str() {
+                "default" => match value {
+                    Err(_) | Ok(Value::Null) => Ok(filter.arg(0).cloned().unwrap_or_default()),
+                    Ok(Value::String(text)) if text.is_empty() => {
+                        Ok(filter.arg(0).cloned().unwrap_or_default())
+                    }
+                    value => value,
+                },
+                _ => value.and_then(|value| filter.apply(value)),
+            })
+    }
+}
+
+impl Filter {
+    fn arg(&self, index: usize) -> Option<&Value> {
+        self.args.get(index)
+    }
+
+    fn apply(&self, value: Value) -> Result<Value, String> {
+        let text = || match &value {
+            Value::String(text) => Ok(text.clone()),
+            Value::Number(_) | Value::Bool(_) => Ok(value.to_string()),
+            value => Err(self.expected("a string", value)),
+        };
+        match self.name.as_str() {
+            "upper" => Ok(Value::String(text()?.to_uppercase())),
+            "lower" => Ok(Value::String(text()?.to_lowercase())),
+            "capitalize" => {
+                let text = text()?;
+                let mut chars = text.chars();
+                Ok(Value::String(match chars.next() {
+                    Some(first) => first.to_uppercase().chain(chars).collect(),
+                    None => text,
+                }))
+            }
+            "trim" => Ok(Value::String(text()?.trim().to_string())),
+            "length" => match &value {
+                Value::String(text) => Ok(Value::from(text.chars().count())),
+                Value::Array(items) => Ok(Value::from(items.len())),
+                Value::Object(object) => Ok(Value::from(object.len())),
+                value => Err(self.expected("a string, an array or an object", value)),
+            },
+            "number" => {
+                let number = match &value {
+                    Value::Number(number) => number.as_f64(),
+                    Value::String(text) => text.trim().parse::<f64>().ok(),
+                    _ => None,
+                }
+                .ok_or_else(|| self.expected("a number", &value))?;
+                match self.arg(0) {
+                    Some(decimals) => {
+                        let decimals = self.count_arg(decimals)?;
+                        if decimals > MAX_DECIMALS {
+                            return Err(format!(
+                                "filter 'number' formats at most {} decimals, found {}",
+                                MAX_DECIMALS, decimals
+                            ));
+                        }
+                        Ok(Value::String(format!("{:.*}", decimals, number)))
+                    }
+                    None => Ok(Number::from_f64(number).map_or(Value::Null, Value::Number)),
+                }
+            }
+            "truncate" => {
+                let text = text()?;
+                let length = match self.arg(0) {
+                    Some(length) => self.count_arg(length)?,
+                    None => return Err("filter 'truncate' expects a length".to_string()),
+                };
+                let suffix = match self.arg(1) {
+                    Some(Value::String(suffix)) => suffix.clone(),
+                    Some(suffix) => suffix.to_string(),
+                    None => "...".to_string(),
+                };
+                if text.chars().count() <= length {
+                    return Ok(Value::String(text));
+                }
+                let truncated: String = text.chars().take(length).collect();
+                Ok(Value::String(format!("{}{}", truncated.trim_end(), suffix)))
+            }
+            "join" => {
+                let separator = match self.arg(0) {
+                    Some(Value::String(separator)) => separator.clone(),
+                    Some(separator) => separator.to_string(),
+                    None => ", ".to_string(),
+                };
+                let items = match &value {
+                    Value::Array(items) => items,
+                    value => return Err(self.expected("an array", value)),
+                };
+                let items = items
+                    .iter()
+                    .map(|item| match item {
+                        Value::String(text) => Ok(text.clone()),
+                        Value::Number(_) | Value::Bool(_) => Ok(item.to_string()),
+                        item => Err(self.expected("an array of strings or numbers", item)),
+                    })
+                    .collect::<Result<Vec<String>, String>>()?;
+                Ok(Value::String(items.join(&separator)))
+            }
+            "date" => {
+                let date = DateTime::from_value(&value).ok_or_else(|| {
+                    format!(
+                        "filter 'date' expects an ISO 8601 date or a Unix timestamp, found {}",
+                        value
+                    )
+                })?;
+                let format = match self.arg(0) {
+                    Some(Value::String(format)) => format.as_str(),
+                    _ => "%Y-%m-%d",
+                };
+                Ok(Value::String(date.format(format)))
+            }
+            "json" => Ok(Value::String(value.to_string())),
+            _ => Ok(value),
+        }
+    }
+
+    fn count_arg(&self, arg: &Value) -> Result<usize, String> {
+        arg.as_u64()
+            .and_then(|count| usize::try_from(count).ok())
+            .ok_or_else(|| {
+                format!(
+                    "filter '{}' expects a whole number argument, found {}",
+                    self.name, arg
+                )
+            })
+    }
+
+    fn expected(&self, expected: &str, value: &Value) -> String {
+        format!(
+            "filter '{}' expects {}, found {}",
+            self.name,
+            expected,
+            type_name(value)
+        )
+    }
+}
+
+// Splits on `separator` outside of single and double quotes.
+fn split_unquoted(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quote = None;
+    let mut escaped = false;
+    let mut start = 0;
+    for (index, ch) in text.char_indices() {
+        match (quote, ch) {
+            _ if escaped => escaped = false,
+            (Some(_), '\\') => escaped = true,
+            (Some(open), ch) if ch == open => quote = None,
+            (None, '"' | '\'') => quote = Some(ch),
+            (None, ch) if ch == separator => {
+                parts.push(&text[start..index]);
+                start = index + ch.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+// A quoted string, a number, or else the trimmed text as a string.
+fn parse_arg(arg: &str) -> Value {
+    let arg = arg.trim();
+    let unquoted = arg
+        .strip_prefix('"')
+        .and_then(|arg| arg.strip_suffix('"'))
+        .or_else(|| {
+            arg.strip_prefix('\'')
+                .and_then(|arg| arg.strip_suffix('\''))
+        });
+    if let Some(text) = unquoted {
+        let mut unescaped = String::new();
+        let mut chars = text.chars();
+        while let Some(ch) = chars.next() {
+            match ch {
+                '\\' => unescaped.extend(chars.next()),
+                ch => unescaped.push(ch),
+            }
+        }
+        return Value::String(unescaped);
+    }
+    if let Ok(number) = arg.parse::<i64>() {
+        return Value::from(number);
+    }
+    match arg.parse::<f64>().ok().and_then(Number::from_f64) {
+        Some(number) => Value::Number(number),
+        None => Value::String(arg.to_string()),
+    }
+}
+
+// A date and time of day, as written in the data; time zones are ignored.
+#[derive(Debug, PartialEq)]
+struct DateTime {
+    year: i64,
+    month: u32,
+    day: u32,
+    hour: u32,
+    minute: u32,
+    second: u32,
+}
+
+impl DateTime {
+    // `2024-03-05`, `2024-03-05T14:30`, `2024-03-05 14:30:00.000Z` or seconds since 1970.
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Number(number) => {
+                let seconds = number
+                    .as_i64()
+                    .or_else(|| number.as_f64().map(|n| n as i64))?;
+                let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+                let time = seconds.rem_euclid(86400) as u32;
+                Some(DateTime {
+                    year,
+                    month,
+                    day,
+                    hour: time / 3600,
+                    minute: time % 3600 / 60,
+                    second: time % 60,
+                })
+            }
+            Value::String(text) => {
+                let text = text.trim();
+                let number = |range: std::ops::Range<usize>| -> Option<u32> {
+                    let digits = text.get(range)?;
+                    digits
+                        .bytes()
+                        .all(|byte| byte.is_ascii_digit())
+                        .then(|| digits.parse().ok())?
+                };
+                let date = DateTime {
+                    year: i64::from(number(0..4)?),
+                    month: number(5..7)?,
+                    day: number(8..10)?,
+                    hour: number(11..13).unwrap_or(0),
+                    minute: number(14..16).unwrap_or(0),
+                    second: number(17..19).unwrap_or(0),
+                };
+                let separators = text.get(4..5) == Some("-") && text.get(7..8) == Some("-");
+                let valid = (1..=12).contains(&date.month)
+                    && date.day >= 1
+                    && date.day <= days_in_month(date.year, date.month)
+                    && date.hour < 24
+                    && date.minute < 60
+                    && date.second < 60;
+                (separators && valid).then_some(date)
+            }
+            _ => None,
+        }
+    }
+
+    /*
+       Formats the date with `%Y` (year), `%m` (month, 01-12), `%d` (day, 01-31), `%e`
+       (day, 1-31), `%H`, `%M`, `%S` (time), `%B`, `%b` (month name, full and short), `%A`,
+       `%a` (weekday name, full and short) and `%%`.
+    */
+    fn format(&self, format: &str) -> String {
+        let weekday = (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7);
+        let month = MONTHS[self.month as usize - 1];
+        let weekday = WEEKDAYS[weekday as usize];
+
+        let mut formatted = String::new();
+        let mut chars = format.chars();
+        while let Some(ch) = chars.next() {
+            if ch != '%' {
+                formatted.push(ch);
+                continue;
+            }
+            match chars.next() {
+                Some('Y') => formatted.push_str(&self.year.to_string()),
+                Some('m') => formatted.push_str(&format!("{:02}", self.month)),
+                Some('d') => formatted.push_str(&format!("{:02}", self.day)),
+                Some('e') => formatted.push_str(&self.day.to_string()),
+                Some('H') => formatted.push_str(&format!("{:02}", self.hour)),
+                Some('M') => formatted.push_str(&format!("{:02}", self.minute)),
+                Some('S') => formatted.push_str(&format!("{:02}", self.second)),
+                Some('B') => formatted.push_str(month),
+                Some('b') => formatted.push_str(&month[..3]),
+                Some('A') => formatted.push_str(weekday),
+                Some('a') => formatted.push_str(&weekday[..3]),
+                Some('%') => formatted.push('%'),
+                Some(other) => {
+                    formatted.push('%');
+                    formatted.push(other);
+                }
+                None => formatted.push('%'),
+            }
+        }
+        formatted
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// Days since 1970-01-01 of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year =
+        (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// The date `days` after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod filters_tests {
+    use serde_json::{json, Value};
+
+    use crate::filters::Placeholder;
+
+    fn apply(placeholder: &str, value: Value) -> Result<Value, String> {
+        Placeholder::parse(placeholder)?.apply(Ok(value))
+    }
+
+    #[test]
+    fn test_parse_placeholder() {
+        let placeholder =
+            Placeholder::parse(r#" labels["a|b"] | truncate: 10, "…" | raw "#).unwrap();
+        assert_eq!(placeholder.path, r#"labels["a|b"]"#);
+        assert_eq!(placeholder.filters[0].name, "truncate");
+        assert_eq!(placeholder.filters[0].args, vec![json!(10), json!("…")]);
+        assert!(placeholder.raw);
+
+        assert_eq!(
+            Placeholder::parse("title | shout").unwrap_err(),
+            "unknown filter 'shout', expected one of upper, lower, capitalize, trim, length, number, truncate, default, join, date, raw, json"
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        assert_eq!(apply("name | upper", json!("Ada")), Ok(json!("ADA")));
+        assert_eq!(
+            apply("name | trim | lower | capitalize", json!("  éMILE ")),
+            Ok(json!("Émile"))
+        );
+        assert_eq!(apply("price | number:2", json!(12.3456)), Ok(json!("12.35")));
+        assert_eq!(apply("price | number:0", json!("12.5")), Ok(json!("12")));
+        assert_eq!(
+            apply("price | number:20", json!(0.5)),
+            Ok(json!("0.50000000000000000000"))
+        );
+        assert_eq!(
+            apply("price | number:1000000000", json!(0.5)),
+            Err("filter 'number' formats at most 20 decimals, found 1000000000".to_string())
+        );
+        assert_eq!(
+            apply("bio | truncate:9", json!("The quick brown fox")),
+            Ok(json!("The quick..."))
+        );
+        assert_eq!(
+            apply(r#"bio | truncate:4,"…""#, json!("The quick")),
+            Ok(json!("The…"))
+        );
+        assert_eq!(
+            apply("bio | truncate:40", json!("Short")),
+            Ok(json!("Short"))
+        );
+        assert_eq!(
+            apply(r#"tags | join:", ""#, json!(["a", 1, true])),
+            Ok(json!("a, 1, true"))
+        );
+        assert_eq!(
+            apply("tags | join | upper", json!(["a", "b"])),
+            Ok(json!("A, B"))
+        );
+        assert_eq!(apply("tags | length", json!(["a", "b"])), Ok(json!(2)));
+        assert_eq!(apply("tags | json", json!(["a"])), Ok(json!(r#"["a"]"#)));
+
+        assert_eq!(
+            apply("price | number:2", json!("free")),
+            Err("filter 'number' expects a number, found string".to_string())
+        );
+        assert_eq!(
+            apply("name | upper", json!({})),
+            Err("filter 'upper' expects a string, found object".to_string())
+        );
+    }
+
+    #[test]
+    fn test_default_filter() {
+        let placeholder = Placeholder::parse(r#"title | default:"Untitled" | upper"#).unwrap();
+        assert_eq!(
+            placeholder.apply(Err("missing".to_string())),
+            Ok(json!("UNTITLED"))
+        );
+        assert_eq!(placeholder.apply(Ok(json!(""))), Ok(json!("UNTITLED")));
+        assert_eq!(placeholder.apply(Ok(json!("Hi"))), Ok(json!("HI")));
+
+        let placeholder = Placeholder::parse("title | upper").unwrap();
+        assert_eq!(
+            placeholder.apply(Err("missing".to_string())),
+            Err("missing".to_string())
+        );
+    }
+
+    #[test]
+    fn test_date_filter() {
+        assert_eq!(
+            apply("at | date", json!("2024-02-29T14:05:09Z")),
+            Ok(json!("2024-02-29"))
+        );
+        assert_eq!(
+            apply(
+                r#"at | date:"%A %e %B %Y, %H:%M:%S""#,
+                json!("2024-02-29T14:05:09Z")
+            ),
+            Ok(json!("Thursday 29 February 2024, 14:05:09"))
+        );
+        assert_eq!(
+            apply(r#"at | date:"%a %d %b %Y %H:%M""#, json!(0)),
+            Ok(json!("Thu 01 Jan 1970 00:00"))
+        );
+        assert_eq!(
+            apply(r#"at | date:"%Y-%m-%d %H:%M""#, json!(1709215509)),
+            Ok(json!("2024-02-29 14:05"))
+        );
+        assert_eq!(apply("at | date", json!(-86400)), Ok(json!("1969-12-31")));
+        assert!(apply("at | date", json!("2023-02-29")).is_err());
+        assert!(apply("at | date", json!("yesterday")).is_err());
+    }
+}
@@ -0,0 +1,343 @@
+use indexmap::IndexMap;
+use serde_json::Value;
+
+use crate::data_path;
+use crate::html::escape;
+use crate::parser::{Attributes, HtmlElement, Node};
+use crate::schema::{FieldType, SchemaField};
+
+// Adds and removes repeater items and turns the form into JSON on submit. Item names
+// are `selector[index].field`, which the serializer maps back to arrays.
+const FORM_SCRIPT: &str = r#"
+(function () {
+  var form = document.currentScript.parentElement;
+  form.addEventListener('click', function (event) {
+    var add = event.target.closest('[data-slk-add]');
+    if (add) {
+      var repeater = add.closest('[data-slk-repeater]');
+      var index = Number(repeater.getAttribute('data-slk-next'));
+      repeater.setAttribute('data-slk-next', index + 1);
+      var html = repeater.querySelector(':scope > template').innerHTML
+        .split(repeater.getAttribute('data-slk-placeholder')).join(index);
+      repeater.querySelector(':scope > .slk-repeater-items').insertAdjacentHTML('beforeend', html);
+    }
+    var remove = event.target.closest('[data-slk-remove]');
+    if (remove) {
+      remove.closest('.slk-repeater-item').remove();
+    }
+  });
+  form.slabkitData = function () {
+    var data = {};
+    form.querySelectorAll('[name]').forEach(function (input) {
+      var value = input.type === 'checkbox' ? input.checked
+        : input.type === 'number' ? (input.value === '' ? null : Number(input.value))
+        : input.value;
+      var keys = input.name.replace(/\[(\d+)\]/g, '.$1').split('.');
+      var target = data;
+      keys.forEach(function (key, i) {
+        if (i === keys.length - 1) {
+          target[key] = value;
+        } else {
+          if (target[key] === undefined) {
+            target[key] = /^\d+$/.test(keys[i + 1]) ? [] : {};
+          }
+          target = target[key];
+        }
+      });
+    });
+    return JSON.parse(JSON.stringify(data, function (key, value) {
+      return Array.isArray(value) ? value.filter(function (item) { return item !== undefined; }) : value;
+    }));
+  };
+  form.addEventListener('submit', function (event) {
+    event.preventDefault();
+    form.querySelector('.slk-form-output').textContent = JSON.stringify(form.slabkitData(), null, 2);
+  });
+})();
+"#;
+
+/*
+   @param fields: &[SchemaField]
+   @param data: &Value
+   @return Node
+
+   Builds an HTML `<form>` with one labelled input per schema field. Input `name`s are the
+   field selectors, array fields become repeaters with add/remove buttons, and inputs are
+   pre-filled from `data` (pass `Value::Null` for an empty form). Submitting the form
+   prints the entered data as JSON below it.
+*/
+pub fn generate_form(fields: &[SchemaField], data: &Value) -> Node {
+    let mut children = fields_to_nodes(fields, data, "", 0);
+    children.push(element(
+        "button",
+        vec![("type", "submit".to_string())],
+        vec![text("Generate data")],
+    ));
+    children.push(element(
+        "pre",
+        vec![("class", "slk-form-output".to_string())],
+        vec![],
+    ));
+    children.push(element(
+        "script",
+        vec![],
+        vec![Node::Text(FORM_SCRIPT.to_string(), None)],
+    ));
+
+    element("form", vec![("class", "slk-form".to_string())], children)
+}
+
+fn fields_to_nodes(fields: &[SchemaField], data: &Value, prefix: &str, depth: usize) -> Vec<Node> {
+    fields
+        .iter()
+        .map(|field| field_to_node(field, data, prefix, depth))
+        .collect()
+}
+
+fn field_to_node(field: &SchemaField, data: &Value, prefix: &str, depth: usize) -> Node {
+    let name = if prefix.is_empty() {
+        field.selector.clone()
+    } else {
+        format!("{}.{}", prefix, field.selector)
+    };
+    let value = lookup(data, &field.selector);
+
+    match &field.field_type {
+        FieldType::Array => repeater(field, value, &name, depth),
+        FieldType::Object => element(
+            "fieldset",
+            vec![("class", "slk-object".to_string())],
+            std::iter::once(element("legend", vec![], vec![text(&field.title)]))
+                .chain(fields_to_nodes(&field.schema, value, &name, depth))
+                .collect(),
+        ),
+        _ => input_field(field, value, &name),
+    }
+}
+
+fn input_field(field: &SchemaField, value: &Value, name: &str) -> Node {
+    let id = field_id(name);
+    let mut attributes = vec![("id", id.clone()), ("name", escape(name))];
+    if let Some(placeholder) = &field.placeholder {
+        attributes.push(("placeholder", escape(placeholder)));
+    }
+    if field.required {
+        attributes.push(("required", String::new()));
+    }
+
+    let input = match &field.field_type {
+        FieldType::Text => element("textarea", attributes, vec![text(&value_to_string(value))]),
+        FieldType::Boolean => {
+            attributes.push(("type", "checkbox".to_string()));
+            attributes.push(("value", "true".to_string()));
+            if value.as_bool() == Some(true) {
+                attributes.push(("checked", "checked".to_string()));
+            }
+            element("input", attributes, vec![])
+        }
+        field_type => {
+            let input_type = match field_type {
+                FieldType::Number => "number".to_string(),
+                FieldType::Input(input_type) => escape(input_type),
+                _ => "text".to_string(),
+            };
+            attributes.push(("type", input_type));
+            attributes.push(("value", escape(&value_to_string(value))));
+            element("input", attributes, vec![])
+        }
+    };
+
+    element(
+        "div",
+        vec![("class", "slk-field".to_string())],
+        vec![
+            element("label", vec![("for", id)], vec![text(&field.title)]),
+            input,
+        ],
+    )
+}
+
+/*
+   An array field: the existing items, a `<template>` used by the script to add new
+   ones and the add button. Each nesting level uses its own index placeholder so nested
+   repeaters can be added independently.
+*/
+fn repeater(field: &SchemaField, value: &Value, name: &str, depth: usize) -> Node {
+    let placeholder = format!("__index{}__", depth);
+    let items = value.as_array().cloned().unwrap_or_default();
+
+    let item_nodes: Vec<Node> = items
+        .iter()
+        .enumerate()
+        .map(|(index, item)| repeater_item(field, item, &format!("{}[{}]", name, index), depth))
+        .collect();
+    let template = repeater_item(
+        field,
+        &Value::Null,
+        &format!("{}[{}]", name, placeholder),
+        depth,
+    );
+
+    element(
+        "fieldset",
+        vec![
+            ("class", "slk-repeater".to_string()),
+            ("data-slk-repeater", escape(name)),
+            ("data-slk-placeholder", placeholder),
+            ("data-slk-next", items.len().to_string()),
+        ],
+        vec![
+            element("legend", vec![], vec![text(&field.title)]),
+            element(
+                "div",
+                vec![("class", "slk-repeater-items".to_string())],
+                item_nodes,
+            ),
+            element("template", vec![], vec![template]),
+            element(
+                "button",
+                vec![
+                    ("type", "button".to_string()),
+                    ("data-slk-add", "true".to_string()),
+                ],
+                vec![text(&format!("Add {}", field.title))],
+            ),
+        ],
+    )
+}
+
+fn repeater_item(field: &SchemaField, item: &Value, name: &str, depth: usize) -> Node {
+    let mut children = if field.schema.is_empty() {
+        // An array of plain values: a single input per item.
+        let item_field = SchemaField {
+            title: field.title.clone(),
+            placeholder: field.placeholder.clone(),
+            field_type: FieldType::String,
+            selector: String::new(),
+            required: field.required,
+            schema: Vec::new(),
+        };
+        vec![input_field(&item_field, item, name)]
+    } else {
+        fields_to_nodes(&field.schema, item, name, depth + 1)
+    };
+    children.push(element(
+        "button",
+        vec![
+            ("type", "button".to_string()),
+            ("data-slk-remove", "true".to_string()),
+        ],
+        vec![text("Remove")],
+    ));
+
+    element(
+        "div",
+        vec![("class", "slk-repeater-item".to_string())],
+        children,
+    )
+}
+
+fn element(tag: &str, attributes: Vec<(&str, String)>, children: Vec<Node>) -> Node {
+    Node::Element(HtmlElement {
+        tag: tag.to_string(),
+        attributes: attributes
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value))
+            .collect::<Attributes>(),
+        children: children.into_iter().map(Box::new).collect(),
+        attribute_locations: IndexMap::new(),
+    })
+}
+
+fn text(content: &str) -> Node {
+    Node::Text(escape(content), None)
+}
+
+// The id of the input named `name`. Underscores are kept so the `__indexN__` placeholders
+// of repeater templates are replaced in ids too, and each added item gets its own.
+fn field_id(name: &str) -> String {
+    let id: String = name
+        .chars()
+        .map(|ch| {
+            if ch.is_alphanumeric() || ch == '_' {
+                ch
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("slk-field-{}", id)
+}
+
+fn lookup<'a>(data: &'a Value, selector: &str) -> &'a Value {
+    if selector.is_empty() {
+        return data;
+    }
+    data_path::lookup(data, selector).unwrap_or(&Value::Null)
+}
+
+fn value_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod form_tests {
+    use serde_json::json;
+
+    use crate::{form::generate_form, schema::parse_schema};
+
+    #[test]
+    fn test_form_from_schema() {
+        let schema = parse_schema(
+            r#"[
+                { "title": "Title", "type": "string", "selector": "section.title", "placeholder": "Enter a title" },
+                { "title": "People", "type": "array", "selector": "section.people", "schema": {
+                    "name": { "title": "Name", "type": "string" }
+                } }
+            ]"#,
+        )
+        .unwrap();
+        let html = generate_form(
+            &schema,
+            &json!({ "section": { "title": "Hero", "people": [{ "name": "Regis" }] } }),
+        )
+        .node_to_html();
+
+        assert!(html.starts_with("<form"));
+        assert!(html.contains(r#"name="section.title""#));
+        assert!(html.contains(r#"value="Hero""#));
+        assert!(html.contains(r#"placeholder="Enter a title""#));
+        assert!(html.contains(r#"<label for="slk-field-section-title">Title</label>"#));
+        assert!(html.contains(r#"name="section.people[0].name""#));
+        assert!(html.contains(r#"value="Regis""#));
+        assert!(html.contains(r#"name="section.people[__index0__].name""#));
+        assert!(html.contains(r#"data-slk-next="1""#));
+        // The script replaces the placeholder in ids too, so added items don't share one.
+        assert!(html.contains(r#"id="slk-field-section-people-__index0__--name""#));
+        assert!(html.contains(r#"<label for="slk-field-section-people-__index0__--name">"#));
+        assert!(html.contains(r#"id="slk-field-section-people-0--name""#));
+    }
+
+    #[test]
+    fn test_form_values_with_path_grammar() {
+        let schema = parse_schema(
+            r#"[
+                { "title": "First name", "type": "string", "selector": "labels[\"first name\"]" },
+                { "title": "Lead", "type": "string", "selector": "people[0].name" }
+            ]"#,
+        )
+        .unwrap();
+        let html = generate_form(
+            &schema,
+            &json!({ "labels": { "first name": "First" }, "people": [{ "name": "Regis" }] }),
+        )
+        .node_to_html();
+
+        assert!(html.contains(r#"value="First""#));
+        assert!(html.contains(r#"value="Regis""#));
+    }
+}
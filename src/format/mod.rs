@@ -0,0 +1,319 @@
+use crate::html::is_void_element;
+use crate::lexer::RAW_TEXT_ELEMENTS;
+use crate::parser::{HtmlElement, Node};
+
+// Elements whose content is kept exactly as written.
+const PREFORMATTED_ELEMENTS: [&str; 2] = ["pre", "textarea"];
+
+// Elements laid out as blocks, around which line breaks don't show. Every other element,
+// custom and `slk-` elements included, may be inline.
+const BLOCK_ELEMENTS: [&str; 44] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "head",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "tbody",
+    "td",
+    "tfoot",
+    "th",
+    "thead",
+    "tr",
+    "ul",
+    "slk-section",
+];
+
+// A piece of the content of an element: a child element, or text without line breaks.
+enum Item<'a> {
+    Text(String),
+    Element(&'a HtmlElement),
+}
+
+// What separates two items, or an item and the tags of its parent, in the source.
+#[derive(Clone, PartialEq)]
+enum Gap {
+    Nothing,
+    Spaces(String),
+    LineBreak,
+}
+
+/*
+   @param nodes: &[Node]
+   @param indent: usize
+   @return String
+
+   Pretty prints parsed template nodes without changing what the page renders: line
+   breaks of the source are kept and re-indented by `indent` spaces per level, and new
+   ones are only added between elements next to a block element, never next to text, so
+   phrasing content stays on one line. Root nodes are separated by a blank line. Scripts,
+   styles, `slk-previewdata`, `slk-dataschema` and preformatted elements are left
+   untouched. Formatting is idempotent.
+*/
+pub fn format_nodes(nodes: &[Node], indent: usize) -> String {
+    let (items, gaps) = layout(None, nodes.iter());
+    let mut output = String::new();
+    for (index, item) in items.iter().enumerate() {
+        if index > 0 {
+            match breaks(None, &items, &gaps, index) {
+                true => output.push_str("\n\n"),
+                false => output.push_str(&spaces(&gaps[index])),
+            }
+        }
+        output.push_str(&write_item(item, 0, indent));
+    }
+    if !output.is_empty() {
+        output.push('\n');
+    }
+    output
+}
+
+fn write_item(item: &Item, depth: usize, indent: usize) -> String {
+    match item {
+        Item::Text(text) => text.clone(),
+        Item::Element(element) => write_element(element, depth, indent),
+    }
+}
+
+fn write_element(element: &HtmlElement, depth: usize, indent: usize) -> String {
+    let open = format!("<{}{}>", element.tag, element.attributes_html());
+    let close = format!("</{}>", element.tag);
+    let tag = element.tag.to_ascii_lowercase();
+
+    if is_void_element(&tag) {
+        return open;
+    }
+    if RAW_TEXT_ELEMENTS.contains(&tag.as_str()) || PREFORMATTED_ELEMENTS.contains(&tag.as_str()) {
+        return format!("{}{}{}", open, raw_content(element), close);
+    }
+
+    let parent = Some(tag.as_str());
+    let (items, gaps) = layout(parent, element.children.iter().map(Box::as_ref));
+    let mut output = open;
+    for index in 0..=items.len() {
+        if breaks(parent, &items, &gaps, index) {
+            let level = if index < items.len() {
+                depth + 1
+            } else {
+                depth
+            };
+            output.push('\n');
+            output.push_str(&" ".repeat(level * indent));
+        } else {
+            output.push_str(&spaces(&gaps[index]));
+        }
+        if let Some(item) = items.get(index) {
+            output.push_str(&write_item(item, depth + 1, indent));
+        }
+    }
+    output.push_str(&close);
+    output
+}
+
+/*
+   Splits the children of an element, or the root nodes when `parent` is `None`, into
+   items, text being split at its line breaks, and the gaps around them: `gaps[i]` comes
+   before `items[i]`, and the last gap before the closing tag.
+*/
+fn layout<'a>(
+    parent: Option<&str>,
+    children: impl Iterator<Item = &'a Node>,
+) -> (Vec<Item<'a>>, Vec<Gap>) {
+    let mut items = Vec::new();
+    let mut gaps = Vec::new();
+    let mut gap = Gap::Nothing;
+
+    for child in children {
+        let text = match child {
+            Node::Element(element) => {
+                gaps.push(std::mem::replace(&mut gap, Gap::Nothing));
+                items.push(Item::Element(element));
+                continue;
+            }
+            Node::Text(text, _) => text,
+        };
+        // Whitespace after the last word, kept in the text if more words follow on the line.
+        let mut trailing = String::new();
+        let mut word = String::new();
+        for (is_space, run) in runs(text) {
+            if !is_space {
+                word.push_str(&std::mem::take(&mut trailing));
+                word.push_str(run);
+                continue;
+            }
+            if !run.contains('\n') {
+                match word.is_empty() {
+                    true => gap = join(gap, Gap::Spaces(run.to_string())),
+                    false => trailing.push_str(run),
+                }
+                continue;
+            }
+            if !word.is_empty() {
+                gaps.push(std::mem::replace(&mut gap, Gap::Nothing));
+                items.push(Item::Text(std::mem::take(&mut word)));
+            }
+            trailing.clear();
+            gap = Gap::LineBreak;
+        }
+        if !word.is_empty() {
+            gaps.push(std::mem::replace(&mut gap, Gap::Nothing));
+            items.push(Item::Text(word));
+            gap = match trailing.is_empty() {
+                true => Gap::Nothing,
+                false => Gap::Spaces(trailing),
+            };
+        }
+    }
+    gaps.push(gap);
+    if parent.is_none() {
+        // Whitespace around the root nodes isn't rendered.
+        gaps[0] = Gap::Nothing;
+    }
+    (items, gaps)
+}
+
+// Whether the gap before `items[index]`, or before the closing tag, is a line break.
+fn breaks(parent: Option<&str>, items: &[Item], gaps: &[Gap], index: usize) -> bool {
+    if gaps[index] == Gap::LineBreak {
+        return true;
+    }
+    let is_block = |item: Option<&Item>| match item {
+        Some(Item::Element(element)) => is_block_element(&element.tag),
+        Some(Item::Text(_)) => false,
+        None => parent.is_some_and(is_block_element),
+    };
+    let before = index.checked_sub(1).map(|index| &items[index]);
+    let after = items.get(index);
+    let next_to_text = [before, after]
+        .iter()
+        .any(|item| matches!(item, Some(Item::Text(_))));
+    // Root nodes are sections of the template, which aren't rendered next to each other.
+    !next_to_text && (parent.is_none() || is_block(before) || is_block(after))
+}
+
+fn is_block_element(tag: &str) -> bool {
+    BLOCK_ELEMENTS.contains(&tag.to_ascii_lowercase().as_str())
+}
+
+fn join(gap: Gap, other: Gap) -> Gap {
+    match (gap, other) {
+        (Gap::LineBreak, _) | (_, Gap::LineBreak) => Gap::LineBreak,
+        (Gap::Spaces(spaces), Gap::Spaces(other)) => Gap::Spaces(spaces + &other),
+        (Gap::Nothing, gap) | (gap, Gap::Nothing) => gap,
+    }
+}
+
+fn spaces(gap: &Gap) -> String {
+    match gap {
+        Gap::Spaces(spaces) => spaces.clone(),
+        _ => String::new(),
+    }
+}
+
+// The text cut into runs of whitespace and of other characters, flagged `true` for whitespace.
+fn runs(text: &str) -> Vec<(bool, &str)> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for (index, ch) in text.char_indices() {
+        let is_space = ch.is_whitespace();
+        if index > start && text[start..].starts_with(char::is_whitespace) != is_space {
+            runs.push((!is_space, &text[start..index]));
+            start = index;
+        }
+    }
+    if start < text.len() {
+        runs.push((
+            text[start..].starts_with(char::is_whitespace),
+            &text[start..],
+        ));
+    }
+    runs
+}
+
+fn raw_content(element: &HtmlElement) -> String {
+    element
+        .children
+        .iter()
+        .map(|child| child.node_to_html())
+        .collect()
+}
+
+#[cfg(test)]
+mod format_tests {
+    use crate::format::format_nodes;
+    use crate::parser::Parser;
+
+    fn format(source: &str) -> String {
+        let nodes = Parser::new(source.to_string()).parse_nodes().unwrap();
+        format_nodes(&nodes, 3)
+    }
+
+    #[test]
+    fn test_format_template() {
+        let source = r#"<slk-section><section   class="hero"><h1>  Hello   </h1>
+        <p>
+  Line one
+     line two</p><br/><script>if (a<b) {  go(); }</script></section></slk-section>
+<slk-previewdata>{"title": "Hero", "tags": [1, 2]}</slk-previewdata>"#;
+
+        let formatted = format(source);
+        assert_eq!(
+            formatted,
+            r#"<slk-section>
+   <section class="hero">
+      <h1>  Hello   </h1>
+      <p>
+         Line one
+         line two</p>
+      <br><script>if (a<b) {  go(); }</script>
+   </section>
+</slk-section>
+
+<slk-previewdata>{"title": "Hero", "tags": [1, 2]}</slk-previewdata>
+"#
+        );
+        assert_eq!(format(&formatted), formatted);
+    }
+
+    #[test]
+    fn test_format_keeps_phrasing_content() {
+        let source = "<div><p>Total: <b>!{price}!</b>, due <i>today</i>.</p><span>a</span><em>b</em>\n</div>";
+        assert_eq!(
+            format(source),
+            "<div>\n   <p>Total: <b>!{price}!</b>, due <i>today</i>.</p>\n   <span>a</span><em>b</em>\n</div>\n"
+        );
+
+        let source = "<p>\n  Some <b>bold</b>\n  text</p>";
+        assert_eq!(format(source), "<p>\n   Some <b>bold</b>\n   text</p>\n");
+    }
+}
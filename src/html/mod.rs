@@ -1,25 +1,340 @@
+use serde_json::Value;
 
-// pub struct  HTML {
-
-// }
-
-// impl  HTML {
-//     let html_tags: Vec<&str> = vec![
-//     "html", "head", "title", "body", "h1", "h2", "h3", "h4", "h5", "h6",
-//     "p", "br", "hr", "blockquote", "pre", "ol", "ul", "li", "dl", "dt",
-//     "dd", "div", "span", "a", "b", "i", "u", "strong", "em", "mark", "small",
-//     "del", "ins", "sub", "sup", "abbr", "cite", "code", "kbd", "var",
-//     "samp", "bdo", "q", "img", "audio", "video", "source", "track",
-//     "map", "area", "figure", "figcaption", "table", "caption", "thead",
-//     "tbody", "tfoot", "tr", "td", "th", "col", "colgroup", "form",
-//     "input", "textarea", "button", "select", "option", "optgroup",
-//     "label", "fieldset", "legend", "datalist", "output", "progress",
-//     "meter", "script", "noscript", "canvas", "meta", "link", "style",
-//     "base", "header", "nav", "section", "article", "aside", "footer",
-//     "main", "summary", "details", "time", "dialog", "template",
-//     "iframe", "embed", "object", "param", "picture", "source", "svg",
-//     "math"
-// ];
-
-    
-// }
\ No newline at end of file
+/*
+   @param text: &str
+   @return String
+
+   Escapes the characters that are significant in HTML text and attribute values.
+*/
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+// Elements that never have content or a closing tag
+pub const VOID_ELEMENTS: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+pub fn is_void_element(tag: &str) -> bool {
+    VOID_ELEMENTS.contains(&tag.to_ascii_lowercase().as_str())
+}
+
+/*
+   Where a placeholder is in the page, which decides how its value is escaped: HTML text
+   and attributes, URL attributes (`href`, `src`...), CSS (`style` attributes and
+   elements), JavaScript (`<script>` elements and `on*` attributes) or an HTML document
+   in an attribute (`srcdoc`), which the browser decodes and then parses as HTML.
+*/
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Context {
+    Html,
+    Url,
+    Css,
+    Script,
+    Document,
+}
+
+// Attributes whose value is a URL.
+const URL_ATTRIBUTES: [&str; 12] = [
+    "action",
+    "background",
+    "cite",
+    "formaction",
+    "href",
+    "icon",
+    "longdesc",
+    "manifest",
+    "poster",
+    "src",
+    "srcset",
+    "xlink:href",
+];
+
+// URL schemes values may use; URLs without a scheme are relative and always allowed.
+const SAFE_URL_SCHEMES: [&str; 4] = ["http", "https", "mailto", "tel"];
+
+// What a URL with an unsafe scheme is replaced with.
+pub const BLOCKED_URL: &str = "about:invalid#slabkit-blocked";
+
+pub fn attribute_context(attribute: &str) -> Context {
+    let attribute = attribute.to_ascii_lowercase();
+    if attribute.starts_with("on") {
+        Context::Script
+    } else if attribute == "style" {
+        Context::Css
+    } else if attribute == "srcdoc" {
+        Context::Document
+    } else if URL_ATTRIBUTES.contains(&attribute.as_str()) {
+        Context::Url
+    } else {
+        Context::Html
+    }
+}
+
+pub fn text_context(tag: &str) -> Context {
+    match tag.to_ascii_lowercase().as_str() {
+        "script" => Context::Script,
+        "style" => Context::Css,
+        _ => Context::Html,
+    }
+}
+
+/*
+   @param value: &Value
+   @param context: Context
+   @param before: &str
+   @return Result<String, String>
+
+   Escapes a string, number or boolean for `context`. `before` is the text that precedes
+   the placeholder in its attribute value or element, which tells whether a URL starts with
+   the value and whether a script has it inside a string literal. In scripts, values are
+   JavaScript literals (a quoted string, a number or a boolean) unless `before` surely
+   ends inside a string literal. A URL starting with an unsafe scheme, such as
+   `javascript:`, is an error.
+*/
+pub fn escape_in_context(value: &Value, context: Context, before: &str) -> Result<String, String> {
+    let text = match value {
+        Value::String(text) => text.clone(),
+        value => value.to_string(),
+    };
+    match context {
+        Context::Html => Ok(escape(&text)),
+        Context::Document => Ok(escape(&escape(&text))),
+        Context::Url if before.trim().is_empty() => {
+            if is_safe_url(&text) {
+                Ok(escape(&encode_url(&text, false)))
+            } else {
+                Err(format!("blocked the unsafe URL '{}'", text))
+            }
+        }
+        Context::Url => Ok(encode_url(&text, true)),
+        Context::Css => Ok(escape_css(&text)),
+        Context::Script if in_js_string(before) => Ok(escape_js_string(&text)),
+        Context::Script => Ok(js_literal(value)),
+    }
+}
+
+// Whether the URL is relative or uses a safe scheme. Browsers ignore control characters
+// and whitespace in schemes, so `java\tscript:` is `javascript:`.
+fn is_safe_url(url: &str) -> bool {
+    let url: String = url.chars().filter(|ch| *ch > ' ').collect();
+    let scheme = match url.find([':', '/', '?', '#']) {
+        Some(end) if url[end..].starts_with(':') => &url[..end],
+        _ => return true,
+    };
+    SAFE_URL_SCHEMES.contains(&scheme.to_ascii_lowercase().as_str())
+}
+
+// Percent-encodes a whole URL, keeping its delimiters, or with `component` a part of one,
+// keeping only unreserved characters.
+fn encode_url(text: &str, component: bool) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        let keep = byte.is_ascii_alphanumeric()
+            || b"-_.~".contains(&byte)
+            || (!component && b":/?#[]@!$&'()*+,;=%".contains(&byte));
+        if keep {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+// Escapes everything but letters, digits, spaces and `-_,.#%` as CSS hex escapes, so a
+// value can't end the declaration, the rule or the `<style>` element.
+fn escape_css(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        if ch.is_alphanumeric() || " -_,.#%".contains(ch) {
+            escaped.push(ch);
+        } else {
+            escaped.push_str(&format!("\\{:x} ", ch as u32));
+        }
+    }
+    escaped
+}
+
+// Escapes a value for a JavaScript string literal in any kind of quotes, leaving no
+// character that could end the literal, the `<script>` element or an attribute.
+fn escape_js_string(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            '"' | '\'' | '`' | '$' | '<' | '>' | '&' | '=' | '\u{2028}' | '\u{2029}' => {
+                escaped.push_str(&format!("\\u{:04x}", ch as u32))
+            }
+            ch if ch.is_control() => escaped.push_str(&format!("\\u{:04x}", ch as u32)),
+            ch => escaped.push(ch),
+        }
+    }
+    escaped
+}
+
+/*
+   The value as a JavaScript literal. Characters that could end the `<script>` element, an
+   attribute or a comment are escaped, so the literal stays a single value wherever the
+   placeholder is in the script.
+*/
+fn js_literal(value: &Value) -> String {
+    let mut literal = String::new();
+    for ch in value.to_string().chars() {
+        match ch {
+            '<' | '>' | '&' | '\'' | '\u{2028}' | '\u{2029}' => {
+                literal.push_str(&format!("\\u{:04x}", ch as u32))
+            }
+            '/' => literal.push_str("\\/"),
+            ch => literal.push(ch),
+        }
+    }
+    literal
+}
+
+/*
+   Whether a script ending with `before` is inside a `'` or `"` string literal. Without
+   a full JavaScript parser this only holds when the strings and comments of `before` are
+   certain: after a `/` that may start a regular expression, a template literal or an
+   HTML-like comment, it returns false.
+*/
+fn in_js_string(before: &str) -> bool {
+    let chars: Vec<char> = before.chars().collect();
+    let starts_with = |index: usize, text: &str| {
+        text.chars()
+            .enumerate()
+            .all(|(offset, ch)| chars.get(index + offset) == Some(&ch))
+    };
+    let mut quote = None;
+    let mut index = 0;
+    while index < chars.len() {
+        match (quote, chars[index]) {
+            (Some(_), '\\') => index += 1,
+            (Some(_), '\n' | '\r') => return false,
+            (Some(open), ch) if ch == open => quote = None,
+            (Some(_), _) => {}
+            (None, '/') if starts_with(index, "//") => {
+                match chars[index..].iter().position(|ch| *ch == '\n') {
+                    Some(end) => index += end,
+                    None => return false,
+                }
+            }
+            (None, '/') if starts_with(index, "/*") => {
+                match (index + 2..chars.len()).find(|end| starts_with(*end, "*/")) {
+                    Some(end) => index = end + 1,
+                    None => return false,
+                }
+            }
+            (None, '/' | '`') => return false,
+            (None, _) if starts_with(index, "<!--") || starts_with(index, "-->") => return false,
+            (None, ch @ ('"' | '\'')) => quote = Some(ch),
+            (None, _) => {}
+        }
+        index += 1;
+    }
+    quote.is_some()
+}
+
+#[cfg(test)]
+mod html_tests {
+    use serde_json::json;
+
+    use crate::html::{attribute_context, escape_in_context, Context};
+
+    #[test]
+    fn test_escape_in_context() {
+        let escape = |value: serde_json::Value, context: Context, before: &str| {
+            escape_in_context(&value, context, before)
+        };
+
+        assert_eq!(
+            escape(json!("a b\"<c>"), Context::Url, ""),
+            Ok("a%20b%22%3Cc%3E".to_string())
+        );
+        assert_eq!(
+            escape(json!("https://x.com/?a=1&b=é"), Context::Url, " "),
+            Ok("https://x.com/?a=1&amp;b=%C3%A9".to_string())
+        );
+        assert_eq!(
+            escape(json!("a&b/c"), Context::Url, "/search?q="),
+            Ok("a%26b%2Fc".to_string())
+        );
+        assert_eq!(
+            escape(json!(" Java\tScript:alert(1)"), Context::Url, ""),
+            Err("blocked the unsafe URL ' Java\tScript:alert(1)'".to_string())
+        );
+        assert!(escape(json!("data:text/html,<b>"), Context::Url, "").is_err());
+        assert!(escape(json!("mailto:a@b.c"), Context::Url, "").is_ok());
+        assert!(escape(json!("/path:with/colon"), Context::Url, "").is_ok());
+
+        assert_eq!(
+            escape(
+                json!("Inter, sans-serif; } body { x: url(y)"),
+                Context::Css,
+                "font:"
+            ),
+            Ok("Inter, sans-serif\\3b  \\7d  body \\7b  x\\3a  url\\28 y\\29 ".to_string())
+        );
+
+        assert_eq!(
+            escape(json!("</script>\"'"), Context::Script, "var a = 'x "),
+            Ok("\\u003c/script\\u003e\\u0022\\u0027".to_string())
+        );
+        assert_eq!(
+            escape(json!("Ada"), Context::Script, "var a = 'it\\'s' + "),
+            Ok("\"Ada\"".to_string())
+        );
+        assert_eq!(
+            escape(json!(3), Context::Script, "var count = "),
+            Ok("3".to_string())
+        );
+
+        // Quotes in comments, regular expressions and template literals don't open strings.
+        let count = json!("1; alert(document.cookie)");
+        for before in [
+            "\n// Don't touch\nvar count = ",
+            "/* it's */ var count = ",
+            "var quote = /'/; var count = ",
+            "var a = 1 / 2, b = '",
+            "var message = `x ${",
+            "<!-- it's\nvar count = ",
+        ] {
+            assert_eq!(
+                escape(count.clone(), Context::Script, before),
+                Ok("\"1; alert(document.cookie)\"".to_string()),
+                "{}",
+                before
+            );
+        }
+        assert_eq!(
+            escape(json!("x"), Context::Script, "// it's 'a'\nvar s = 'it\\'s "),
+            Ok("x".to_string())
+        );
+        assert_eq!(
+            escape(json!("*/ alert(1) </script>"), Context::Script, "/* "),
+            Ok("\"*\\/ alert(1) \\u003c\\/script\\u003e\"".to_string())
+        );
+
+        assert_eq!(
+            escape(json!("<script>alert(1)</script>"), Context::Document, "<p>"),
+            Ok("&amp;lt;script&amp;gt;alert(1)&amp;lt;/script&amp;gt;".to_string())
+        );
+        assert_eq!(attribute_context("srcset"), Context::Url);
+        assert!(escape(json!("javascript:alert(1)"), Context::Url, "").is_err());
+    }
+}
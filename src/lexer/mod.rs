@@ -1,44 +1,37 @@
 pub struct InputReader {
     pub input: String,
+    // Byte offset of the next character
     pub index: usize,
     pub line: usize,
     pub col: usize,
+    // Where the token being read started: byte offset, line and column
+    token_start: (usize, usize, usize),
+}
+
+// Byte range of a token in the template source
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 
 pub struct Token {
+    // Line and column of the first character of the token
     pub line: usize,
     pub column: usize,
+    pub span: Span,
     pub token: EToken,
 }
 
-impl Token {
-    pub fn format_unexpected_err(&self) -> String {
-        format!(
-            "Unexpected token {} at line {}, column {}",
-            self.token.to_symbol(),
-            self.line,
-            self.column
-        )
-    }
-
-    pub fn format_unexpected_err_with_expected(&self, expected: EToken) -> String {
-        format!(
-            "Unexpected token {} at line {}, column {}, expected : {}",
-            self.token.to_symbol(),
-            self.line,
-            self.column,
-            expected.to_symbol()
-        )
-    }
-}
-
 #[derive(Debug, PartialEq, Clone)]
+#[allow(non_camel_case_types, clippy::upper_case_acronyms)]
 pub enum EToken {
     LESSTHAN,                 // "<"
     GREATERTHAN,              // ">"
     LESS_THAN_SLASH,          // "</"
+    SLASH_GREATERTHAN,        // "/>"
     PLACEHOLDER_START_SYMBOL, // "!{"
     PLACEHOLDER_END_SYMBOL,   // "}!"
     EQUAL_SYMBOL,             // "="
@@ -53,10 +46,11 @@ impl EToken {
             EToken::LESSTHAN => "<".to_string(),
             EToken::GREATERTHAN => ">".to_string(),
             EToken::LESS_THAN_SLASH => "</".to_string(),
+            EToken::SLASH_GREATERTHAN => "/>".to_string(),
             EToken::PLACEHOLDER_START_SYMBOL => "!{".to_string(),
             EToken::PLACEHOLDER_END_SYMBOL => "}!".to_string(),
             EToken::EQUAL_SYMBOL => "=".to_string(),
-            EToken::APOSTROPHE => "\"".to_string(), // Or use '\''
+            EToken::APOSTROPHE => "'".to_string(),
             EToken::DOT => ".".to_string(),
             EToken::TEXT(content) => content.clone(),
         }
@@ -83,29 +77,35 @@ impl InputReader {
             index: 0,
             line: 1,
             col: 1,
+            token_start: (0, 1, 1),
         }
     }
 
     pub fn last_char(&mut self) -> Option<char> {
-        let current_index = self.index;
-        self.input.chars().nth(current_index - 1)
+        self.input[..self.index].chars().next_back()
     }
     pub fn next_char(&mut self) -> Option<char> {
-        let ch = self.input.chars().nth(self.index);
-        match ch {
-            Some('\n') => {
-                self.col = 0;
-                self.line += 1;
-            }
-            _ => {}
+        let ch = self.peek_char()?;
+        if ch == '\n' {
+            self.col = 0;
+            self.line += 1;
         }
-        self.index += 1;
+        self.index += ch.len_utf8();
         self.col += 1;
-        ch
+        Some(ch)
     }
 
     pub fn peek_char(&self) -> Option<char> {
-        self.input.chars().nth(self.index)
+        self.input[self.index..].chars().next()
+    }
+
+    pub fn peek_nth_char(&self, n: usize) -> Option<char> {
+        self.input[self.index..].chars().nth(n)
+    }
+
+    // Marks the next character as the start of the next token.
+    pub fn start_token(&mut self) {
+        self.token_start = (self.index, self.line, self.col);
     }
 
     pub fn consume_whitespace(&mut self) {
@@ -117,15 +117,24 @@ impl InputReader {
             }
         }
     }
-    pub fn token(&mut self, tokenType: EToken) -> Token {
+    // Token spanning from the last `start_token` call to the current position.
+    pub fn token(&mut self, token_type: EToken) -> Token {
+        let (start, line, column) = self.token_start;
         Token {
-            column: self.col,
-            line: self.line,
-            token: tokenType,
+            column,
+            line,
+            span: Span {
+                start,
+                end: self.index,
+            },
+            token: token_type,
         }
     }
 }
 
+// Elements whose content is not markup and runs until their closing tag.
+pub const RAW_TEXT_ELEMENTS: [&str; 4] = ["script", "style", "slk-previewdata", "slk-dataschema"];
+
 pub struct Lexer {
     reader: InputReader,
 }
@@ -139,8 +148,23 @@ impl Lexer {
 
     pub fn tokenize(&mut self) -> Vec<Token> {
         let mut tokens: Vec<Token> = vec![];
+        // Outside of tags the input is character data, emitted as one TEXT token per run.
+        let mut in_tag = false;
+        let mut raw_text_tag: Option<String> = None;
 
         loop {
+            if !in_tag {
+                self.reader.start_token();
+                let text = match raw_text_tag.take() {
+                    Some(tag) => self.read_raw_text(&tag),
+                    None => self.read_text(),
+                };
+                if !text.is_empty() {
+                    tokens.push(self.reader.token(EToken::TEXT(text)));
+                }
+            }
+
+            self.reader.start_token();
             let ch = self.reader.next_char();
             if ch.is_none() {
                 break;
@@ -151,16 +175,22 @@ impl Lexer {
             }
             match ch.unwrap() {
                 '<' => {
+                    in_tag = true;
                     let next_char = self.reader.peek_char();
                     match next_char {
                         Some('/') => {
-                            tokens.push(self.reader.token(EToken::LESS_THAN_SLASH));
                             self.reader.next_char();
+                            tokens.push(self.reader.token(EToken::LESS_THAN_SLASH));
                         }
                         _ => tokens.push(self.reader.token(EToken::LESSTHAN)),
                     }
                 }
-                '>' => tokens.push(self.reader.token(EToken::GREATERTHAN)),
+                '>' => {
+                    in_tag = false;
+                    raw_text_tag = start_tag_name(&tokens)
+                        .filter(|tag| RAW_TEXT_ELEMENTS.contains(&tag.as_str()));
+                    tokens.push(self.reader.token(EToken::GREATERTHAN));
+                }
                 // '!' => {
                 //     let next_char = self.reader.peek_char();
                 //     match next_char {
@@ -187,7 +217,15 @@ impl Lexer {
                 //         ),
                 //     }
                 // }
-                '=' => tokens.push(self.reader.token(EToken::EQUAL_SYMBOL)),
+                '/' if self.reader.peek_char() == Some('>') => {
+                    self.reader.next_char();
+                    in_tag = false;
+                    tokens.push(self.reader.token(EToken::SLASH_GREATERTHAN));
+                }
+                '=' => {
+                    tokens.push(self.reader.token(EToken::EQUAL_SYMBOL));
+                    self.read_attribute_value(&mut tokens);
+                }
                 // '.' => tokens.push(self.reader.token(EToken::DOT)),
                 '\'' | '\"' => tokens.push(self.reader.token(EToken::APOSTROPHE)),
                 '\n' => {
@@ -197,21 +235,17 @@ impl Lexer {
                     if ch.is_none() {
                         break;
                     }
+                    // Tag or attribute name
                     let mut char_sequence = String::from(ch.unwrap());
-                    loop {
-                        if let Some(next_char) = self.reader.peek_char() {
-                            if next_char.is_alphanumeric()
-                                || ['}', '!', '{', '@', '-', '_', ':', '.', '&', ';', ',']
-                                    .contains(&next_char)
-                            {
-                                char_sequence.push(next_char);
-                                self.reader.next_char();
-                            } else {
-                                break;
-                            }
-                        } else {
+                    while let Some(next_char) = self.reader.peek_char() {
+                        if next_char.is_whitespace()
+                            || ['=', '>', '<', '\'', '"'].contains(&next_char)
+                            || self.at_self_closing_end()
+                        {
                             break;
                         }
+                        char_sequence.push(next_char);
+                        self.reader.next_char();
                     }
                     tokens.push(self.reader.token(EToken::TEXT(char_sequence)));
                 }
@@ -220,12 +254,120 @@ impl Lexer {
 
         tokens
     }
+
+    /*
+       Reads the value following an `=` in a tag verbatim. A quoted value runs up to the
+       quote character that opened it and is emitted between APOSTROPHE tokens, an
+       unquoted value runs up to the next whitespace or `>`.
+    */
+    fn read_attribute_value(&mut self, tokens: &mut Vec<Token>) {
+        self.reader.consume_whitespace();
+        self.reader.start_token();
+        let mut value = String::new();
+
+        match self.reader.peek_char() {
+            Some(quote) if quote == '\'' || quote == '"' => {
+                self.reader.next_char();
+                tokens.push(self.reader.token(EToken::APOSTROPHE));
+                self.reader.start_token();
+                while let Some(ch) = self.reader.peek_char() {
+                    if ch == quote {
+                        break;
+                    }
+                    value.push(ch);
+                    self.reader.next_char();
+                }
+                tokens.push(self.reader.token(EToken::TEXT(value)));
+                self.reader.start_token();
+                if self.reader.next_char().is_some() {
+                    tokens.push(self.reader.token(EToken::APOSTROPHE));
+                }
+            }
+            _ => {
+                while let Some(ch) = self.reader.peek_char() {
+                    if ch.is_whitespace() || ch == '>' || self.at_self_closing_end() {
+                        break;
+                    }
+                    value.push(ch);
+                    self.reader.next_char();
+                }
+                if !value.is_empty() {
+                    tokens.push(self.reader.token(EToken::TEXT(value)));
+                }
+            }
+        }
+    }
+
+    fn at_self_closing_end(&self) -> bool {
+        self.reader.peek_char() == Some('/') && self.reader.peek_nth_char(1) == Some('>')
+    }
+
+    /*
+       Reads character data up to the next tag, keeping spacing and punctuation as written.
+       A `<` that can't start a tag is part of the text.
+    */
+    fn read_text(&mut self) -> String {
+        let mut text = String::new();
+        while let Some(ch) = self.reader.peek_char() {
+            if ch == '<' {
+                match self.reader.peek_nth_char(1) {
+                    Some(next) if next == '/' || next.is_alphabetic() => break,
+                    _ => {}
+                }
+            }
+            text.push(ch);
+            self.reader.next_char();
+        }
+        text
+    }
+
+    // Reads the content of a raw text element up to its closing tag.
+    fn read_raw_text(&mut self, tag: &str) -> String {
+        let mut text = String::new();
+        while let Some(ch) = self.reader.peek_char() {
+            if ch == '<' && self.reader.peek_nth_char(1) == Some('/') {
+                let closes_tag = tag.chars().enumerate().all(|(i, tag_char)| {
+                    self.reader
+                        .peek_nth_char(i + 2)
+                        .is_some_and(|ch| ch.eq_ignore_ascii_case(&tag_char))
+                });
+                if closes_tag {
+                    break;
+                }
+            }
+            text.push(ch);
+            self.reader.next_char();
+        }
+        text
+    }
+}
+
+// Name of the tag being closed by a `>`, when it is a start tag.
+fn start_tag_name(tokens: &[Token]) -> Option<String> {
+    let start = tokens.iter().rposition(|token| {
+        token.token == EToken::LESSTHAN || token.token == EToken::LESS_THAN_SLASH
+    })?;
+    match (
+        &tokens[start].token,
+        tokens.get(start + 1).map(|t| &t.token),
+    ) {
+        (EToken::LESSTHAN, Some(EToken::TEXT(name))) => Some(name.to_lowercase()),
+        _ => None,
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn token_kinds(input: &str) -> Vec<EToken> {
+        Lexer::new(input.to_string())
+            .tokenize()
+            .into_iter()
+            .map(|token| token.token)
+            .collect()
+    }
+
     #[test]
     fn test_placeholder_tokenization() {
         let input = "!{placeholder}! = value";
@@ -233,23 +375,123 @@ mod tests {
         let tokens = lexer.tokenize();
         assert_eq!(
             tokens,
+            vec![Token {
+                token: EToken::TEXT("!{placeholder}! = value".to_string()),
+                line: 1,
+                column: 1,
+                span: Span { start: 0, end: 23 },
+            }]
+        );
+
+        let input = "<p>!{placeholder}! = value</p>";
+        assert_eq!(
+            token_kinds(input),
             vec![
-                Token {
-                    token: EToken::TEXT("!{placeholder}!".to_string()),
-                    line: 1,
-                    column: 16
-                },
-                Token {
-                    token: EToken::EQUAL_SYMBOL,
-                    line: 1,
-                    column: 18
-                },
-                Token {
-                    token: EToken::TEXT("value".to_string()),
-                    line: 1,
-                    column: 24
-                }
+                EToken::LESSTHAN,
+                EToken::TEXT("p".to_string()),
+                EToken::GREATERTHAN,
+                EToken::TEXT("!{placeholder}! = value".to_string()),
+                EToken::LESS_THAN_SLASH,
+                EToken::TEXT("p".to_string()),
+                EToken::GREATERTHAN,
             ]
         );
     }
+
+    #[test]
+    fn test_text_keeps_spacing_and_punctuation() {
+        let input = "<h1>\n  name quick (brown) fox?  jumps &nbsp;&copy; a < b / 'c'\n</h1>";
+        assert_eq!(
+            token_kinds(input)[3],
+            EToken::TEXT(
+                "\n  name quick (brown) fox?  jumps &nbsp;&copy; a < b / 'c'\n".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_attribute_values_are_verbatim() {
+        let input = r#"<a href="https://x.dev/a?b=1#c" title='say "hi"' data-x=1 disabled>"#;
+        assert_eq!(
+            token_kinds(input),
+            vec![
+                EToken::LESSTHAN,
+                EToken::TEXT("a".to_string()),
+                EToken::TEXT("href".to_string()),
+                EToken::EQUAL_SYMBOL,
+                EToken::APOSTROPHE,
+                EToken::TEXT("https://x.dev/a?b=1#c".to_string()),
+                EToken::APOSTROPHE,
+                EToken::TEXT("title".to_string()),
+                EToken::EQUAL_SYMBOL,
+                EToken::APOSTROPHE,
+                EToken::TEXT(r#"say "hi""#.to_string()),
+                EToken::APOSTROPHE,
+                EToken::TEXT("data-x".to_string()),
+                EToken::EQUAL_SYMBOL,
+                EToken::TEXT("1".to_string()),
+                EToken::TEXT("disabled".to_string()),
+                EToken::GREATERTHAN,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_token_positions() {
+        let tokens = Lexer::new("<p>\n  héllo</p>".to_string()).tokenize();
+        let text = &tokens[3];
+        assert_eq!(text.token, EToken::TEXT("\n  héllo".to_string()));
+        assert_eq!((text.line, text.column), (1, 4));
+        assert_eq!(text.span, Span { start: 3, end: 12 });
+
+        let closing = &tokens[4];
+        assert_eq!(closing.token, EToken::LESS_THAN_SLASH);
+        assert_eq!((closing.line, closing.column), (2, 8));
+        assert_eq!(closing.span, Span { start: 12, end: 14 });
+    }
+
+    #[test]
+    fn test_large_template_is_linear() {
+        let section = r#"<div class="card" data-id="!{id}!">
+  <h2>!{section.title}! &amp; friends</h2>
+  <p>Some (long) paragraph of text, with punctuation? Yes / no.</p>
+</div>
+"#;
+        let template = format!("<main>{}</main>", section.repeat(3_000));
+        assert!(template.len() > 400_000);
+
+        let start = std::time::Instant::now();
+        let tokens = Lexer::new(template.clone()).tokenize();
+        let node = crate::parser::Parser::new(template).parse().unwrap();
+        let html = crate::evaluator::Evaluator::new(serde_json::json!({
+            "id": 7,
+            "section": { "title": "Slabkit" }
+        }))
+        .evaluate(node)
+        .unwrap()
+        .node_to_html();
+        let elapsed = start.elapsed();
+
+        assert!(tokens.len() > 60_000);
+        assert_eq!(
+            html.matches("<h2>Slabkit &amp; friends</h2>").count(),
+            3_000
+        );
+        // A quadratic reader, or a placeholder pattern compiled for every text, takes
+        // minutes on this input.
+        assert!(
+            elapsed < std::time::Duration::from_secs(5),
+            "tokenizing, parsing and evaluating took {:?}",
+            elapsed
+        );
+    }
+
+    #[test]
+    fn test_raw_text_elements() {
+        let input = "<script>if (a <b && c) { x = '</p>'; }</script>";
+        assert_eq!(
+            token_kinds(input)[3],
+            EToken::TEXT("if (a <b && c) { x = '</p>'; }".to_string())
+        );
+    }
 }
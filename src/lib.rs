@@ -0,0 +1,15 @@
+pub mod data_path;
+pub mod error;
+pub mod evaluator;
+pub mod file_ops;
+pub mod filters;
+pub mod form;
+pub mod format;
+pub mod html;
+pub mod lexer;
+pub mod logger;
+pub mod parser;
+pub mod schema;
+pub mod server;
+pub mod template;
+pub mod utils;
@@ -1,30 +1,139 @@
-use clap::Parser as ClapParser;
-use evaluator::Evaluator;
-use parser::Parser;
-use warp::Filter;
-
-mod evaluator;
-mod file_ops;
-mod html;
-mod lexer;
-mod logger;
-mod parser;
-mod utils;
+use clap::{Parser as ClapParser, Subcommand};
+use serde_json::{json, Value};
+use slabkit::error::{use_colour, SlabkitError};
+use slabkit::evaluator::{Evaluator, Undefined};
+use slabkit::format::format_nodes;
+use slabkit::parser::Parser;
+use slabkit::schema::{validate_data, SchemaField, ValidationError};
+use slabkit::server::{self, ApiRender, Page};
+use slabkit::template::Template;
+use slabkit::{form, utils};
+use std::io::{self, Write};
+use std::net::IpAddr;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 #[derive(ClapParser, Debug)]
 #[command(author = "Regis Rex https://github.com/regisrex", version = "0.0.1", about ="SlabKit, an html templating engine that is easy to use", long_about = None)]
 pub struct Args {
-    /// Action to perform on the template  ['dev', 'compile']
-    #[arg(short, long)]
-    action: Option<String>,
+    #[command(subcommand)]
+    command: Command,
+}
 
-    /// Path to the template file
-    #[arg(short, long)]
-    template: Option<String>,
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Render a template to HTML
+    Render {
+        #[command(flatten)]
+        input: TemplateArgs,
 
-    /// Path to the output file
-    #[arg(short, long)]
-    output: Option<String>,
+        /// Path to the output file, `-` or nothing for stdout
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// Fail when the data doesn't match the template's schema or a placeholder is missing
+        /// from the data instead of warning
+        #[arg(long)]
+        strict: bool,
+
+        /// What to do with placeholders missing from the data: keep, empty, warn or error.
+        /// Defaults to error with --strict, and keep otherwise
+        #[arg(long)]
+        undefined: Option<Undefined>,
+    },
+
+    /// Render a template, or every template of a directory, and serve it over HTTP
+    Serve {
+        /// Path to the template file, or to a directory of templates
+        path: String,
+
+        /// Path to the data file of a single template. In a directory, each template's data
+        /// is read from the JSON file next to it with the same name, if there is one
+        #[arg(short, long)]
+        data: Option<String>,
+
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1")]
+        host: IpAddr,
+
+        /// Port to listen on
+        #[arg(short, long, default_value_t = 3030)]
+        port: u16,
+
+        /// Fail when the data doesn't match the template's schema or a placeholder is missing
+        /// from the data instead of warning
+        #[arg(long)]
+        strict: bool,
+
+        /// What to do with placeholders missing from the data: keep, empty, warn or error.
+        /// Defaults to error with --strict, and keep otherwise
+        #[arg(long)]
+        undefined: Option<Undefined>,
+
+        /// Rebuild the page when the template, its includes or the data file change (any file
+        /// of a served directory), and reload it in the browser
+        #[arg(short, long)]
+        watch: bool,
+
+        /// Serve the render API of a directory of templates instead of the pages: `POST
+        /// /render` with a `template` name or an inline template `source`, and `data`
+        #[arg(long, conflicts_with_all = ["data", "watch"])]
+        api: bool,
+    },
+
+    /// Check a template for syntax errors, data that doesn't match its schema and broken includes
+    Check {
+        #[command(flatten)]
+        input: TemplateArgs,
+
+        /// What to do with placeholders missing from the data: keep, empty, warn or error
+        #[arg(long, default_value = "error")]
+        undefined: Undefined,
+    },
+
+    /// Format a template
+    Fmt {
+        /// Path to the template file
+        template: String,
+
+        /// Number of spaces per indentation level
+        #[arg(long, default_value_t = 3)]
+        indent: usize,
+
+        /// Write the formatted template back to the file instead of printing it
+        #[arg(short, long, conflicts_with = "check")]
+        write: bool,
+
+        /// Only check that the template is formatted, failing when it isn't
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Print the parsed template as JSON
+    Ast {
+        /// Path to the template file
+        template: String,
+    },
+
+    /// Print the template's data schema as JSON, or its data entry form
+    Schema {
+        #[command(flatten)]
+        input: TemplateArgs,
+
+        /// Generate the HTML data entry form, pre-filled with the data file
+        #[arg(long)]
+        form: bool,
+
+        /// Path to the output file, `-` or nothing for stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct TemplateArgs {
+    /// Path to the template file
+    template: String,
 
     /// Path to the data file
     /// This is the data that will be used to populate the template
@@ -36,31 +145,362 @@ pub struct Args {
 async fn main() {
     let args = Args::parse();
 
-    let action = utils::get_action(&args.action);
+    match args.command {
+        Command::Render {
+            input,
+            output,
+            strict,
+            undefined,
+        } => {
+            let (template, data) = load(&input);
+            let undefined = undefined_policy(undefined, strict);
+            let page = exit_on_error(render(
+                &template,
+                data,
+                &input.template,
+                None,
+                strict,
+                undefined,
+            ));
+            write_output(&utils::get_outfile(&output), page.html);
+        }
+        Command::Serve {
+            path,
+            data,
+            host,
+            port,
+            strict,
+            undefined,
+            watch,
+            api,
+        } => {
+            let address = (host, port).into();
+            let undefined = undefined_policy(undefined, strict);
+            if api {
+                if !Path::new(&path).is_dir() {
+                    exit_on_error::<()>(Err(format!(
+                        "Slabkit error: --api serves a directory of templates, '{}' isn't one",
+                        path
+                    )));
+                }
+                let dir = PathBuf::from(path);
+                let root = dir.clone();
+                let render = move |source: &str, template: &Path, data: Option<Value>| {
+                    render_api(source, template, &root, data, strict, undefined)
+                };
+                server::serve_api(address, dir, render).await;
+            } else if Path::new(&path).is_dir() {
+                if data.is_some() {
+                    exit_on_error::<()>(Err(
+                        "Slabkit error: --data only applies to a single template, a directory's templates read their data from the JSON file next to them".to_string(),
+                    ));
+                }
+                let dir = PathBuf::from(path);
+                let root = dir.clone();
+                let build = move |template: &Path, posted: Option<Value>| {
+                    let data = template.with_extension("json");
+                    let data = data.is_file().then(|| data.to_string_lossy().to_string());
+                    let path = template.to_string_lossy();
+                    build_page(&path, Some(&root), &data, posted, strict, undefined)
+                };
+                server::serve_dir(address, dir, build, watch).await;
+            } else {
+                let build = move |posted: Option<Value>| {
+                    build_page(&path, None, &data, posted, strict, undefined)
+                };
+                server::serve(address, build, watch).await;
+            }
+        }
+        Command::Check { input, undefined } => check(&input, undefined),
+        Command::Fmt {
+            template,
+            indent,
+            write,
+            check,
+        } => format(&template, indent, write, check),
+        Command::Ast { template } => print_ast(&template),
+        Command::Schema {
+            input,
+            form,
+            output,
+        } => {
+            let (template, data) = load(&input);
+            let fields = exit_on_error(
+                template
+                    .schema
+                    .ok_or("Slabkit error: the template has no slk-dataschema section".to_string()),
+            );
+            let content = if form {
+                write_form(&fields, data)
+            } else {
+                let schema: Vec<Value> = fields.iter().map(SchemaField::to_json).collect();
+                serde_json::to_string_pretty(&schema).unwrap_or_default()
+            };
+            write_output(&utils::get_outfile(&output), content);
+        }
+    }
+}
 
-    let template = utils::get_template(&args.template);
-    // println!("Template: {}", template);
+/*
+   Reads, parses and renders the template with its data file, or with the data posted by
+   the live editor, for the server. Unlike `load`, errors are returned, coloured, so the
+   server can show them in the browser.
+*/
+fn build_page(
+    path: &str,
+    root: Option<&Path>,
+    data_path: &Option<String>,
+    posted: Option<Value>,
+    strict: bool,
+    undefined: Undefined,
+) -> Result<Page, String> {
+    let source = utils::get_template(path)?;
+    let data = match posted {
+        Some(data) => Some(data),
+        None => utils::get_data(data_path)?,
+    };
+    let template =
+        Template::parse(source.clone()).map_err(|error| error.render(&source, path, true))?;
 
-    let data = utils::get_data(&args.data);
+    let mut page = render(&template, data.clone(), path, root, strict, undefined)?;
+    page.files.extend(data_path.iter().map(PathBuf::from));
+    page.form = template
+        .schema
+        .as_ref()
+        .map(|fields| write_form(fields, Some(template.render_data(data))));
+    Ok(page)
+}
 
-    let output = utils::get_outfile(&args.output);
+// Reads and parses the template and its data file, exiting on errors.
+fn load(input: &TemplateArgs) -> (Template, Option<Value>) {
+    let source = exit_on_error(utils::get_template(&input.template));
+    let data = exit_on_error(utils::get_data(&input.data));
+    (parse_template(&source, &input.template), data)
+}
 
-    let parser_output_node = Parser::new(template).parse();
+/*
+   Parses the template, or prints its errors with the offending source lines and exits.
+*/
+fn parse_template(source: &str, path: &str) -> Template {
+    match Template::parse(source.to_string()) {
+        Ok(template) => template,
+        Err(error) => exit_with_error(&error, source, path),
+    }
+}
 
-    match parser_output_node {
-        Ok(node) => {
-            let json_value = file_ops::get_json_value_from_template(data.unwrap());
-            let evaluated_node = Evaluator::new(json_value).evaluate(node);
+fn exit_with_error(error: &SlabkitError, source: &str, path: &str) -> ! {
+    eprint!("{}", error.render(source, path, use_colour()));
+    std::process::exit(1);
+}
+
+/*
+   Evaluates the template with its preview data and the data file merged over it. Data
+   that doesn't match the `slk-dataschema` is reported as warnings, or as an error when
+   `strict`.
+*/
+fn render(
+    template: &Template,
+    data: Option<Value>,
+    path: &str,
+    root: Option<&Path>,
+    strict: bool,
+    undefined: Undefined,
+) -> Result<Page, String> {
+    let json_value = template.render_data(data);
+    validate(template, &json_value, strict).map_err(|errors| {
+        let errors: Vec<String> = errors
+            .iter()
+            .map(|error| format!("Slabkit error: {}", error))
+            .collect();
+        errors.join("\n")
+    })?;
+    evaluate(template, json_value, path, root, undefined)
+}
+
+// The `--undefined` policy, which defaults to `error` with `--strict`.
+fn undefined_policy(undefined: Option<Undefined>, strict: bool) -> Undefined {
+    undefined.unwrap_or(if strict {
+        Undefined::Error
+    } else {
+        Undefined::Keep
+    })
+}
+
+// Returns the schema errors of the data when `strict`, and prints them as warnings otherwise.
+fn validate(template: &Template, data: &Value, strict: bool) -> Result<(), Vec<ValidationError>> {
+    if let Some(fields) = &template.schema {
+        if let Err(errors) = validate_data(fields, data) {
+            if strict {
+                return Err(errors);
+            }
+            for error in errors {
+                eprintln!("Slabkit warning: {}", error);
+            }
+        }
+    }
+    Ok(())
+}
+
+/*
+   Renders a template for the render API, with its errors as JSON: syntax errors, schema
+   errors when `strict`, and errors while evaluating, such as broken includes or includes
+   outside of `root`, the served directory.
+*/
+fn render_api(
+    source: &str,
+    path: &Path,
+    root: &Path,
+    data: Option<Value>,
+    strict: bool,
+    undefined: Undefined,
+) -> ApiRender {
+    let template = Template::parse(source.to_string()).map_err(|error| error.to_json())?;
+    let data = template.render_data(data);
+    validate(&template, &data, strict)
+        .map_err(|errors| errors.iter().map(ValidationError::to_json).collect::<Vec<_>>())?;
+    evaluate(&template, data, &path.to_string_lossy(), Some(root), undefined)
+        .map(|page| page.html)
+        .map_err(|error| {
+            vec![json!({
+                "type": "render",
+                "message": error.trim_start_matches("Slabkit error: "),
+            })]
+        })
+}
+
+// The rendered page, along with the template and the files it includes. Includes must be
+// inside `root`, by default the directory of the template.
+fn evaluate(
+    template: &Template,
+    data: Value,
+    path: &str,
+    root: Option<&Path>,
+    undefined: Undefined,
+) -> Result<Page, String> {
+    let mut evaluator = Evaluator::new(data)
+        .with_source_path(Path::new(path))
+        .with_undefined(undefined);
+    if let Some(root) = root {
+        evaluator = evaluator.with_root(root);
+    }
+    let evaluated_nodes = evaluator.evaluate_template(template.body())?;
+    for warning in evaluator.warnings() {
+        eprintln!("Slabkit warning: {}", warning);
+    }
+    let mut files = vec![PathBuf::from(path)];
+    files.extend_from_slice(evaluator.included_files());
+    Ok(Page {
+        html: evaluated_nodes.iter().map(|node| node.node_to_html()).collect(),
+        files,
+        form: None,
+    })
+}
+
+/*
+   Reports every problem of the template: syntax errors, data (the preview data with the
+   data file merged over it) that doesn't match the schema, and errors while evaluating,
+   such as broken includes and, with the `error` policy, placeholders missing from the
+   data. Exits with a non-zero status when there is any.
+*/
+fn check(input: &TemplateArgs, undefined: Undefined) {
+    let (template, data) = load(input);
+    let data = template.render_data(data);
+    let mut failed = false;
+
+    if let Some(fields) = &template.schema {
+        if let Err(errors) = validate_data(fields, &data) {
+            for error in &errors {
+                eprintln!("Slabkit error: {}", error);
+            }
+            failed = true;
+        }
+    }
+    if let Err(error) = evaluate(&template, data, &input.template, None, undefined) {
+        eprintln!("{}", error);
+        failed = true;
+    }
+
+    if failed {
+        std::process::exit(1);
+    }
+    println!("{}: no problems found", input.template);
+}
+
+/*
+   Prints the formatted template, writes it back with `write`, or with `check` exits with
+   a non-zero status when the file isn't formatted.
+*/
+fn format(path: &str, indent: usize, write: bool, check: bool) {
+    let source = exit_on_error(utils::get_template(path));
+    let nodes = match Parser::new(source.clone()).parse_nodes() {
+        Ok(nodes) => nodes,
+        Err(errors) => exit_with_error(&SlabkitError::Parse(errors), &source, path),
+    };
+    let formatted = format_nodes(&nodes, indent);
+
+    if check {
+        if formatted != source {
+            eprintln!("Slabkit error: {} is not formatted", path);
+            std::process::exit(1);
+        }
+    } else if write {
+        exit_on_error(
+            fs::write(path, formatted)
+                .map_err(|error| format!("Slabkit error: couldn't write '{}': {}", path, error)),
+        );
+    } else {
+        write_output(&None, formatted);
+    }
+}
 
-            // Define a warp filter that serves the HTML
-            let html_route = warp::path::end().map(move || warp::reply::html(evaluated_node.node_to_html().clone()));
+/*
+   Prints the template tree as JSON. A broken template still prints the partial tree,
+   followed by its errors and a non-zero exit status.
+*/
+fn print_ast(path: &str) {
+    let source = exit_on_error(utils::get_template(path));
+    let (nodes, errors) = Parser::new(source.clone()).parse_recovering();
+    let ast: Vec<Value> = nodes.iter().map(|node| node.to_json()).collect();
+    write_output(&None, serde_json::to_string_pretty(&ast).unwrap_or_default());
 
-            // Start the warp server
-            println!("Server running at http://localhost:3030");
-            warp::serve(html_route).run(([127, 0, 0, 1], 3030)).await;
+    if !errors.is_empty() {
+        exit_with_error(&SlabkitError::Parse(errors), &source, path);
+    }
+}
+
+/*
+   Generates the data entry form of the template's `slk-dataschema`, pre-filled with the
+   data file when one is given.
+*/
+fn write_form(fields: &[SchemaField], data: Option<Value>) -> String {
+    form::generate_form(fields, &data.unwrap_or_default()).node_to_html()
+}
+
+// Writes to the output file, or to stdout when there is none.
+fn write_output(output: &Option<String>, content: String) {
+    match output {
+        Some(path) => exit_on_error(
+            fs::write(path, content)
+                .map_err(|error| format!("Slabkit error: couldn't write '{}': {}", path, error)),
+        ),
+        None => {
+            // A closed pipe (`slabkit ... | head`) isn't an error.
+            let newline = if content.ends_with('\n') { "" } else { "\n" };
+            if let Err(error) = write!(io::stdout().lock(), "{}{}", content, newline) {
+                if error.kind() != io::ErrorKind::BrokenPipe {
+                    exit_on_error(Err(format!("Slabkit error: couldn't write the output: {}", error)))
+                }
+            }
         }
+    }
+}
+
+fn exit_on_error<T>(result: Result<T, String>) -> T {
+    match result {
+        Ok(value) => value,
         Err(error) => {
-            panic!("Slabkit error: {:?}", error)
+            eprintln!("{}", error);
+            std::process::exit(1);
         }
     }
 }
@@ -1,35 +1,34 @@
-use core::slice;
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use serde_json::{json, Map, Value};
 
+use crate::error::{Location, ParseError, ParseErrorKind};
+use crate::html::is_void_element;
 use crate::lexer::EToken;
 
 use crate::lexer::{Lexer, Token};
 
 #[derive(Debug, PartialEq)]
 pub enum Node {
-    Element(HtmlElement), // tags
-    Text(String),         // Text node
+    Element(HtmlElement),           // tags
+    Text(String, Option<Location>), // Text node, and where it starts in the template if parsed
 }
 
 impl Node {
     pub fn a_string(&self) -> String {
         match self {
             Node::Element(elt) => elt.tag.clone(),
-            Node::Text(t) => t.to_string(),
+            Node::Text(t, _) => t.to_string(),
         }
     }
 
     pub fn node_to_html(&self) -> String {
         match self {
-            Node::Text(text) => text.clone(),
+            Node::Text(text, _) => text.clone(),
             Node::Element(element) => {
-                let mut attributes = String::new();
-                for (key, value) in &element.attributes {
-                    attributes.push_str(&format!(r#" {}="{}""#, key, value));
-                }
+                let attributes = element.attributes_html();
 
-                if element.children.is_empty() {
-                    format!(r#"<{}{} />"#, element.tag, attributes)
+                if is_void_element(&element.tag) {
+                    format!(r#"<{}{}>"#, element.tag, attributes)
                 } else {
                     let mut children_html = String::new();
                     for child in &element.children {
@@ -43,21 +42,68 @@ impl Node {
             }
         }
     }
+
+    // The tree as JSON, for tooling: `{ "type": "element", "tag", "attributes", "children" }`
+    // for elements and `{ "type": "text", "text" }` for text.
+    pub fn to_json(&self) -> Value {
+        match self {
+            Node::Text(text, _) => json!({ "type": "text", "text": text }),
+            Node::Element(element) => json!({
+                "type": "element",
+                "tag": element.tag,
+                "attributes": element
+                    .attributes
+                    .iter()
+                    .map(|(key, value)| (key.clone(), Value::String(value.clone())))
+                    .collect::<Map<String, Value>>(),
+                "children": element
+                    .children
+                    .iter()
+                    .map(|child| child.to_json())
+                    .collect::<Vec<Value>>(),
+            }),
+        }
+    }
 }
 impl Clone for Node {
     fn clone(&self) -> Self {
         match self {
             Node::Element(elt) => Node::Element(elt.clone()),
-            Node::Text(t) => Node::Text(t.clone()),
+            Node::Text(t, location) => Node::Text(t.clone(), *location),
         }
     }
 }
 
+// Attributes in source order, so rendering is reproducible.
+pub type Attributes = IndexMap<String, String>;
+
 #[derive(Debug, PartialEq)]
 pub struct HtmlElement {
     pub tag: String,
-    pub attributes: HashMap<String, String>,
+    pub attributes: Attributes,
     pub children: Vec<Box<Node>>,
+    // Where the value of each attribute starts in the template, for parsed elements.
+    pub attribute_locations: IndexMap<String, Location>,
+}
+
+impl HtmlElement {
+    // The attributes as they appear in the start tag, each preceded by a space.
+    pub fn attributes_html(&self) -> String {
+        let mut attributes = String::new();
+        for (key, value) in &self.attributes {
+            if value.is_empty() {
+                // `disabled` and `disabled=""` are the same attribute
+                attributes.push_str(&format!(" {}", key));
+            } else {
+                attributes.push_str(&format!(
+                    r#" {}="{}""#,
+                    key,
+                    value.replace('"', "&quot;")
+                ));
+            }
+        }
+        attributes
+    }
 }
 
 impl Clone for HtmlElement {
@@ -66,20 +112,33 @@ impl Clone for HtmlElement {
             tag: self.tag.clone(),
             attributes: self.attributes.clone(),
             children: self.children.clone(),
+            attribute_locations: self.attribute_locations.clone(),
         }
     }
 }
 #[derive(Debug, Clone)]
 pub struct Parser {
     pub tokens: Vec<Token>,
+    // The template, to name the quote an unterminated attribute value opened with
+    source: String,
     cursor: usize,
+    // Where errors at the end of the input are reported
+    end: Location,
+    // Errors found so far; parsing carries on after each of them
+    diagnostics: Vec<ParseError>,
+    // Names of the elements being parsed, outermost first
+    open_tags: Vec<String>,
 }
 
 impl Parser {
     pub fn new(template: String) -> Self {
         Self {
-            tokens: Lexer::new(template).tokenize(),
+            end: Location::end_of(&template),
+            tokens: Lexer::new(template.clone()).tokenize(),
+            source: template,
             cursor: 0,
+            diagnostics: Vec::new(),
+            open_tags: Vec::new(),
         }
     }
 
@@ -92,148 +151,432 @@ impl Parser {
         self.current_token()
     }
 
-    fn peek_token(&self) -> Option<&Token> {
+    pub fn peek_token(&self) -> Option<&Token> {
         self.tokens.get(self.cursor + 1)
     }
 
-    pub fn expect_and_consume_token(&mut self, expected: EToken) -> Result<(), String> {
-        if let Some(token) = self.current_token() {
-            if token.token == expected {
+    pub fn expect_and_consume_token(&mut self, expected: EToken) -> Result<(), ParseError> {
+        match self.current_token() {
+            Some(token) if token.token == expected => {
                 self.next_token();
                 Ok(())
-            } else {
-                Err(token.format_unexpected_err_with_expected(expected))
             }
+            Some(token) => Err(ParseError::unexpected_token(
+                token,
+                Some(&format!("`{}`", expected.to_symbol())),
+            )),
+            None => Err(self.end_of_input(Some(&format!("`{}`", expected.to_symbol())))),
+        }
+    }
+
+    fn end_of_input(&self, expected: Option<&str>) -> ParseError {
+        ParseError::new(
+            ParseErrorKind::UnexpectedEndOfInput {
+                expected: expected.map(str::to_string),
+            },
+            self.end,
+        )
+    }
+
+    pub fn parse(&mut self) -> Result<Node, ParseError> {
+        if self.cursor == 0 {
+            // Whitespace before the root element is not part of the document.
+            while let Some(Token {
+                token: EToken::TEXT(text),
+                ..
+            }) = self.current_token()
+            {
+                if !text.trim().is_empty() {
+                    break;
+                }
+                self.next_token();
+            }
+        }
+        let node = self.parse_node();
+        let mut diagnostics = std::mem::take(&mut self.diagnostics);
+        if !diagnostics.is_empty() {
+            return Err(diagnostics.remove(0));
+        }
+        node.ok_or_else(|| self.end_of_input(None))
+    }
+
+    /*
+       Parses every root node of the document. Whitespace between root elements is not
+       part of the document.
+    */
+    pub fn parse_nodes(&mut self) -> Result<Vec<Node>, Vec<ParseError>> {
+        let (nodes, diagnostics) = self.parse_recovering();
+        if diagnostics.is_empty() {
+            Ok(nodes)
         } else {
-            Err("Unexpected end of input".to_string())
+            Err(diagnostics)
         }
     }
 
-    pub fn parse(&mut self) -> Result<Node, String> {
-        let token: Option<Token> = match self.current_token() {
-            Some(token) => Some(token.clone()),
-            None => None,
-        };
-        match token {
-            Some(token) => {
-                let result_node = match &token.token {
-                    EToken::TEXT(string_value) => {
-                        self.next_token();
-                        Ok(Node::Text(string_value.to_string()))
+    /*
+       @return (Vec<Node>, Vec<ParseError>)
+
+       Parses the whole document without stopping at the first error: after an error the
+       parser skips to the next `<` or `</` and carries on, so the result is the partial
+       tree along with every problem found.
+    */
+    pub fn parse_recovering(&mut self) -> (Vec<Node>, Vec<ParseError>) {
+        let mut nodes = Vec::new();
+        while let Some(token) = self.current_token() {
+            match &token.token {
+                EToken::TEXT(text) if text.trim().is_empty() => {
+                    self.next_token();
+                }
+                _ => {
+                    if let Some(node) = self.parse_node() {
+                        nodes.push(node);
                     }
-                    _ => self.parse_block(),
-                };
+                }
+            }
+        }
+        (nodes, std::mem::take(&mut self.diagnostics))
+    }
 
-                result_node
+    fn report(&mut self, error: ParseError) {
+        self.diagnostics.push(error);
+    }
+
+    // Skips tokens up to the next `<` or `</`, where parsing can start again.
+    fn synchronize(&mut self) {
+        while let Some(token) = self.current_token() {
+            match token.token {
+                EToken::LESSTHAN | EToken::LESS_THAN_SLASH => break,
+                _ => {
+                    self.next_token();
+                }
+            }
+        }
+    }
+
+    // A text node or an element; None at the end of the input or when the tokens were skipped.
+    fn parse_node(&mut self) -> Option<Node> {
+        let token = self.current_token()?.clone();
+        match &token.token {
+            EToken::TEXT(string_value) => {
+                self.next_token();
+                Some(Node::Text(
+                    string_value.to_string(),
+                    Some(Location::from(&token)),
+                ))
+            }
+            EToken::LESS_THAN_SLASH => {
+                // A closing tag without a matching open tag
+                let location = self.tokens.get(self.cursor + 1).map(Location::from);
+                if let Some(Token {
+                    token: EToken::TEXT(name),
+                    ..
+                }) = self.peek_token()
+                {
+                    let error = ParseError::new(
+                        ParseErrorKind::UnexpectedClosingTag { tag: name.clone() },
+                        location.unwrap_or(self.end),
+                    );
+                    self.report(error);
+                } else {
+                    self.report(ParseError::unexpected_token(&token, None));
+                }
+                self.skip_closing_tag();
+                None
+            }
+            EToken::LESSTHAN => self.parse_block(),
+            _ => {
+                self.report(ParseError::unexpected_token(&token, None));
+                self.next_token();
+                self.synchronize();
+                None
             }
-            None => Err("Unexpected error".to_string()),
         }
     }
-    pub fn parse_block(&mut self) -> Result<Node, String> {
-        let _ = self.expect_and_consume_token(EToken::LESSTHAN); // First get rid of the symbol
-        let current_token = self.current_token();
+
+    fn parse_block(&mut self) -> Option<Node> {
+        // First get rid of the symbol
+        if let Err(error) = self.expect_and_consume_token(EToken::LESSTHAN) {
+            self.report(error);
+            self.next_token();
+            self.synchronize();
+            return None;
+        }
         // Parse the tag name
-        let tag_name = if let Some(Token {
-            token: EToken::TEXT(name),
-            ..
-        }) = current_token
-        {
-            name.clone()
-        } else {
-            return Err(current_token.unwrap().format_unexpected_err());
+        let (tag_name, open_tag) = match self.current_token() {
+            Some(
+                token @ Token {
+                    token: EToken::TEXT(name),
+                    ..
+                },
+            ) => (name.clone(), Location::from(token)),
+            Some(token) => {
+                let error = ParseError::unexpected_token(token, Some("a tag name"));
+                self.report(error);
+                self.synchronize();
+                return None;
+            }
+            None => {
+                self.report(self.end_of_input(Some("a tag name")));
+                return None;
+            }
         };
         self.next_token();
 
-        let mut attributes = HashMap::new();
+        let mut element = HtmlElement {
+            tag: tag_name.clone(),
+            attributes: Attributes::new(),
+            children: Vec::new(),
+            attribute_locations: IndexMap::new(),
+        };
 
         loop {
-            let token = match self.current_token() {
-                Some(token) => token.clone(),
-                None => break,
+            let token = match self.current_token().cloned() {
+                Some(token) => token,
+                None => {
+                    self.report(self.end_of_input(Some("`>`")));
+                    return Some(Node::Element(element));
+                }
             };
-
             match &token.token {
                 EToken::TEXT(attr_name) => {
                     self.next_token();
-                    self.expect_and_consume_token(EToken::EQUAL_SYMBOL)?; // Expect '='
+                    if self.current_token().map(|t| &t.token) != Some(&EToken::EQUAL_SYMBOL) {
+                        // Boolean attribute such as `disabled`
+                        self.insert_attribute(&mut element, &token, String::new(), None);
+                        continue;
+                    }
+                    self.next_token(); // Consume '='
 
-                    if let Some(Token {
-                        token: EToken::APOSTROPHE,
-                        ..
-                    }) = self.current_token()
-                    {
-                        self.next_token();
-
-                        // if let Some(Token {
-                        //     token: EToken::TEXT(attr_value),
-                        //     ..
-                        // }) = self.current_token()
-                        // {
-                        //     attributes.insert(attr_name.clone(), attr_value.clone());
-                        //     self.next_token();
-                        // }
-
-                        let mut attr_full_value = String::new();
-
-                        while let Some(Token {
-                            token: EToken::TEXT(attr_value),
-                            ..
-                        }) = self.current_token()
-                        {
-                            attr_full_value += &format!(" {}", attr_value).to_string();
+                    match self.current_token().map(|t| t.token.clone()) {
+                        Some(EToken::APOSTROPHE) => {
+                            let quote = self.current_token().and_then(|quote| {
+                                self.source[quote.span.start..quote.span.end].chars().next()
+                            });
                             self.next_token();
-                        }
-                        attributes.insert(attr_name.clone(), attr_full_value.clone());
+                            let mut attr_value = String::new();
+                            let mut location = None;
+                            if let Some(
+                                value_token @ Token {
+                                    token: EToken::TEXT(value),
+                                    ..
+                                },
+                            ) = self.current_token()
+                            {
+                                attr_value = value.clone();
+                                location = Some(Location::from(value_token));
+                                self.next_token();
+                            }
+                            self.insert_attribute(&mut element, &token, attr_value, location);
 
-                        self.expect_and_consume_token(EToken::APOSTROPHE)?;
-                    } else {
-                        return Err("Expected an attribute value".to_string());
+                            if self.current_token().map(|t| &t.token) != Some(&EToken::APOSTROPHE) {
+                                // An unterminated value runs to the end of the input.
+                                let quote =
+                                    quote.map_or(EToken::APOSTROPHE.to_symbol(), String::from);
+                                let error = self.end_of_input(Some(&format!("`{}`", quote)));
+                                self.report(error);
+                                return Some(Node::Element(element));
+                            }
+                            self.next_token();
+                        }
+                        // Unquoted value
+                        Some(EToken::TEXT(attr_value)) => {
+                            let location = self.current_token().map(Location::from);
+                            self.next_token();
+                            self.insert_attribute(&mut element, &token, attr_value, location);
+                        }
+                        _ => {
+                            let location =
+                                self.current_token().map(Location::from).unwrap_or(self.end);
+                            self.report(ParseError::new(
+                                ParseErrorKind::MissingAttributeValue {
+                                    attribute: attr_name.clone(),
+                                },
+                                location,
+                            ));
+                        }
                     }
                 }
-                EToken::GREATERTHAN => break,
+                EToken::GREATERTHAN => {
+                    self.next_token(); // Consume '>'
+                    break;
+                }
+                EToken::SLASH_GREATERTHAN => {
+                    // `<tag ... />` has no children
+                    self.next_token();
+                    return Some(Node::Element(element));
+                }
+                EToken::LESSTHAN | EToken::LESS_THAN_SLASH => {
+                    // The start tag was never closed; carry on with what follows as its content.
+                    self.report(ParseError::unexpected_token(&token, Some("`>`")));
+                    break;
+                }
                 // Error.
-                _ => return Err(token.format_unexpected_err()),
+                _ => {
+                    self.report(ParseError::unexpected_token(&token, Some("`>`")));
+                    self.next_token();
+                }
             }
         }
 
-        self.expect_and_consume_token(EToken::GREATERTHAN)?; // Consume '>'
+        if is_void_element(&tag_name) {
+            self.skip_void_end_tag(&tag_name);
+            return Some(Node::Element(element));
+        }
 
         // Parse children (sub-elements or text content)
-        let mut children = Vec::new();
+        self.open_tags.push(tag_name.clone());
         while let Some(token) = self.current_token() {
-            match &token.token {
-                EToken::LESS_THAN_SLASH => {
-                    // End of the element
+            if token.token != EToken::LESS_THAN_SLASH {
+                // Parse a child node (could be text or another element)
+                if let Some(child) = self.parse_node() {
+                    element.children.push(Box::new(child));
+                }
+                continue;
+            }
+
+            // End of the element
+            let name_token = self.peek_token().cloned();
+            match name_token {
+                Some(
+                    ref token @ Token {
+                        token: EToken::TEXT(ref name),
+                        ..
+                    },
+                ) if *name != tag_name => {
+                    let ancestors = &self.open_tags[..self.open_tags.len() - 1];
+                    if ancestors.contains(name) {
+                        // Closes an enclosing element: this one was left open.
+                        let error = ParseError::new(
+                            ParseErrorKind::MismatchedClosingTag {
+                                expected: tag_name.clone(),
+                                found: name.clone(),
+                            },
+                            Location::from(token),
+                        )
+                        .with_open_tag(open_tag);
+                        self.report(error);
+                        self.open_tags.pop();
+                        return Some(Node::Element(element));
+                    }
+                    self.report(ParseError::new(
+                        ParseErrorKind::UnexpectedClosingTag { tag: name.clone() },
+                        Location::from(token),
+                    ));
+                    self.skip_closing_tag();
+                }
+                Some(Token {
+                    token: EToken::TEXT(_),
+                    ..
+                }) => {
                     self.next_token(); // Consume '</'
-                    self.expect_and_consume_token(EToken::TEXT(tag_name.clone()))?;
-                    self.expect_and_consume_token(EToken::GREATERTHAN)?; // Consume '>'
-                    return Ok(Node::Element(HtmlElement {
-                        tag: tag_name,
-                        attributes,
-                        children,
-                    }));
+                    self.next_token(); // Consume the tag name
+                    if let Err(error) = self.expect_and_consume_token(EToken::GREATERTHAN) {
+                        self.report(error);
+                    }
+                    self.open_tags.pop();
+                    return Some(Node::Element(element));
                 }
-                _ => {
-                    // Parse a child node (could be text or another element)
-                    children.push(Box::new(self.parse()?));
+                Some(token) => {
+                    self.report(ParseError::unexpected_token(&token, Some("a tag name")));
+                    self.next_token();
+                    self.synchronize();
+                }
+                None => {
+                    self.next_token();
                 }
             }
         }
         // In case the tag wasn't closed
-        Err(format!("Unclosed tag: <{}>", tag_name))
+        self.report(
+            ParseError::new(ParseErrorKind::UnclosedTag { tag: tag_name }, self.end)
+                .with_open_tag(open_tag),
+        );
+        self.open_tags.pop();
+        Some(Node::Element(element))
+    }
+
+    // Duplicated attributes are reported and, as in browsers, the first one is kept.
+    fn insert_attribute(
+        &mut self,
+        element: &mut HtmlElement,
+        name: &Token,
+        value: String,
+        location: Option<Location>,
+    ) {
+        let attribute = name.token.to_symbol();
+        if element.attributes.contains_key(&attribute) {
+            self.report(ParseError::new(
+                ParseErrorKind::DuplicateAttribute { attribute },
+                Location::from(name),
+            ));
+        } else {
+            if let Some(location) = location {
+                element
+                    .attribute_locations
+                    .insert(attribute.clone(), location);
+            }
+            element.attributes.insert(attribute, value);
+        }
+    }
+
+    // Consumes `</name>`, or as much of it as is there.
+    fn skip_closing_tag(&mut self) {
+        self.next_token(); // Consume '</'
+        if let Some(Token {
+            token: EToken::TEXT(_),
+            ..
+        }) = self.current_token()
+        {
+            self.next_token();
+        }
+        if let Some(Token {
+            token: EToken::GREATERTHAN,
+            ..
+        }) = self.current_token()
+        {
+            self.next_token();
+        }
+    }
+
+    // Void elements have no end tag, but `<br> </br>` is accepted and the end tag dropped.
+    fn skip_void_end_tag(&mut self, tag_name: &str) {
+        let mut cursor = self.cursor;
+        if let Some(Token {
+            token: EToken::TEXT(text),
+            ..
+        }) = self.tokens.get(cursor)
+        {
+            if text.trim().is_empty() {
+                cursor += 1;
+            }
+        }
+        let end_tag = (
+            self.tokens.get(cursor).map(|t| &t.token),
+            self.tokens.get(cursor + 1).map(|t| &t.token),
+            self.tokens.get(cursor + 2).map(|t| &t.token),
+        );
+        if let (
+            Some(EToken::LESS_THAN_SLASH),
+            Some(EToken::TEXT(name)),
+            Some(EToken::GREATERTHAN),
+        ) = end_tag
+        {
+            if name.eq_ignore_ascii_case(tag_name) {
+                self.cursor = cursor + 3;
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod parser_tests {
 
-    use crate::{lexer::EToken, parser::Parser};
+    use crate::{error::ParseErrorKind, lexer::EToken, parser::Parser};
 
     #[test]
     fn test_parser_utils() {
         let input = "<div>Hello</div>".to_string();
         let mut parser = Parser::new(input.clone());
-        let len = parser.tokens.len();
         // Print the tokens and the first token from the parser
         for token in parser.tokens.iter() {
             println!("{:?}", token);
@@ -253,4 +596,149 @@ mod parser_tests {
         let node = parser.parse();
         println!("Result node after parsing: {:?}", node)
     }
+
+    #[test]
+    fn test_attributes_round_trip() {
+        let input = r#"<a href="https://x.dev/a?b=1&c=2#top" title='say "hi"'>Link</a>"#;
+        let node = Parser::new(input.to_string()).parse().unwrap();
+        assert_eq!(
+            node.node_to_html(),
+            r#"<a href="https://x.dev/a?b=1&c=2#top" title="say &quot;hi&quot;">Link</a>"#
+        );
+
+        let input = "<input type=checkbox checked value=''></input>";
+        match Parser::new(input.to_string()).parse().unwrap() {
+            crate::parser::Node::Element(elt) => {
+                assert_eq!(elt.attributes["type"], "checkbox");
+                assert_eq!(elt.attributes["checked"], "");
+                assert_eq!(elt.attributes["value"], "");
+            }
+            node => panic!("Expected an element, got {:?}", node),
+        }
+    }
+
+    #[test]
+    fn test_void_and_self_closing_elements() {
+        let input = r#"<div><br><img src="a.png" alt="A"/><hr /> <br> </br><span/></div>"#;
+        let node = Parser::new(input.to_string()).parse().unwrap();
+        match &node {
+            crate::parser::Node::Element(elt) => {
+                let tags: Vec<String> = elt.children.iter().map(|child| child.a_string()).collect();
+                assert_eq!(tags, vec!["br", "img", "hr", " ", "br", "span"]);
+            }
+            node => panic!("Expected an element, got {:?}", node),
+        }
+        assert_eq!(
+            node.node_to_html(),
+            r#"<div><br><img src="a.png" alt="A"><hr> <br><span></span></div>"#
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = Parser::new("<div>\n  <p>Hi</div>".to_string())
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::MismatchedClosingTag {
+                expected: "p".to_string(),
+                found: "div".to_string(),
+            }
+        );
+        assert_eq!((error.location.line, error.location.column), (2, 10));
+        let open_tag = error.open_tag.unwrap();
+        assert_eq!((open_tag.line, open_tag.column), (2, 4));
+
+        let error = Parser::new("<ul>\n<li>".to_string()).parse().unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnclosedTag {
+                tag: "li".to_string()
+            }
+        );
+        assert_eq!((error.location.line, error.location.column), (2, 5));
+
+        let error = Parser::new("<a href=>x</a>".to_string())
+            .parse()
+            .unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::MissingAttributeValue {
+                attribute: "href".to_string()
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "attribute `href` has no value after `=` at line 1, column 9"
+        );
+
+        let error = Parser::new("<a b='".to_string()).parse().unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnexpectedEndOfInput {
+                expected: Some("`'`".to_string())
+            }
+        );
+        let error = Parser::new("<a b=\"x".to_string()).parse().unwrap_err();
+        assert_eq!(
+            error.kind,
+            ParseErrorKind::UnexpectedEndOfInput {
+                expected: Some("`\"`".to_string())
+            }
+        );
+    }
+
+    #[test]
+    fn test_recovers_and_reports_every_error() {
+        let input = "<ul>\n  <li class=>One</li>\n  </p>\n  <li>Two\n</ul>\n<p>After</p>";
+        let (nodes, diagnostics) = Parser::new(input.to_string()).parse_recovering();
+
+        let kinds: Vec<ParseErrorKind> = diagnostics.into_iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ParseErrorKind::MissingAttributeValue {
+                    attribute: "class".to_string()
+                },
+                ParseErrorKind::UnexpectedClosingTag {
+                    tag: "p".to_string()
+                },
+                ParseErrorKind::MismatchedClosingTag {
+                    expected: "li".to_string(),
+                    found: "ul".to_string()
+                },
+            ]
+        );
+        let html: Vec<String> = nodes.iter().map(|node| node.node_to_html()).collect();
+        assert_eq!(
+            html,
+            vec![
+                "<ul>\n  <li>One</li>\n  \n  <li>Two\n</li></ul>",
+                "<p>After</p>"
+            ]
+        );
+
+        let (nodes, diagnostics) = Parser::new("<div><p>Text".to_string()).parse_recovering();
+        assert_eq!(nodes.len(), 1);
+        assert_eq!(diagnostics.len(), 2);
+    }
+
+    #[test]
+    fn test_attribute_order_and_duplicates() {
+        let input = r#"<img style="width:1px" src="a.png" alt="A" data-x=1 hidden>"#;
+        let node = Parser::new(input.to_string()).parse().unwrap();
+        assert_eq!(node.node_to_html(), input.replace("=1", r#"="1""#));
+
+        let mut parser = Parser::new(r#"<p id="a" class="x" id="b">Hi</p>"#.to_string());
+        let (nodes, diagnostics) = parser.parse_recovering();
+        assert_eq!(nodes[0].node_to_html(), r#"<p id="a" class="x">Hi</p>"#);
+        assert_eq!(
+            diagnostics[0].kind,
+            ParseErrorKind::DuplicateAttribute {
+                attribute: "id".to_string()
+            }
+        );
+        assert_eq!(diagnostics[0].location.column, 21);
+    }
 }
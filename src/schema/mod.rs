@@ -0,0 +1,487 @@
+use std::fmt;
+
+use serde_json::{json, Map, Value};
+
+use crate::data_path::{self, type_name, DataPath, Segment};
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum FieldType {
+    String,
+    Text,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    // Any other string input type (`email`, `url`, `date`, `color`, ...).
+    Input(String),
+}
+
+impl FieldType {
+    pub fn from_name(name: &str) -> Self {
+        match name {
+            "string" | "" => FieldType::String,
+            "text" | "textarea" => FieldType::Text,
+            "number" => FieldType::Number,
+            "boolean" => FieldType::Boolean,
+            "array" => FieldType::Array,
+            "object" => FieldType::Object,
+            other => FieldType::Input(other.to_string()),
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        match self {
+            FieldType::String => "string",
+            FieldType::Text => "text",
+            FieldType::Number => "number",
+            FieldType::Boolean => "boolean",
+            FieldType::Array => "array",
+            FieldType::Object => "object",
+            FieldType::Input(input_type) => input_type,
+        }
+    }
+
+    pub fn matches(&self, value: &Value) -> bool {
+        match self {
+            FieldType::String | FieldType::Text | FieldType::Input(_) => value.is_string(),
+            FieldType::Number => value.is_number(),
+            FieldType::Boolean => value.is_boolean(),
+            FieldType::Array => value.is_array(),
+            FieldType::Object => value.is_object(),
+        }
+    }
+}
+
+/*
+   One entry of an `slk-dataschema` section.
+
+   `selector` is the path of the value in the render data, relative to the enclosing
+   array item for fields nested in an array schema.
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct SchemaField {
+    pub title: String,
+    pub placeholder: Option<String>,
+    pub field_type: FieldType,
+    pub selector: String,
+    pub required: bool,
+    pub schema: Vec<SchemaField>,
+}
+
+impl SchemaField {
+    // The field in the `slk-dataschema` array form, with the optional keys left out when unset.
+    pub fn to_json(&self) -> Value {
+        let mut field = Map::new();
+        field.insert("title".to_string(), Value::from(self.title.clone()));
+        if let Some(placeholder) = &self.placeholder {
+            field.insert("placeholder".to_string(), Value::from(placeholder.clone()));
+        }
+        field.insert("type".to_string(), Value::from(self.field_type.name()));
+        field.insert("selector".to_string(), Value::from(self.selector.clone()));
+        if self.required {
+            field.insert("required".to_string(), Value::Bool(true));
+        }
+        if !self.schema.is_empty() {
+            field.insert(
+                "schema".to_string(),
+                Value::Array(self.schema.iter().map(SchemaField::to_json).collect()),
+            );
+        }
+        Value::Object(field)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum ValidationErrorKind {
+    MissingField,
+    WrongType { expected: String, found: String },
+    UnknownKey,
+}
+
+/*
+   A mismatch between the render data and the schema. `selector` is the full path of
+   the value in the data, with `[index]` for array items.
+*/
+#[derive(Debug, PartialEq, Clone)]
+pub struct ValidationError {
+    pub selector: String,
+    pub kind: ValidationErrorKind,
+}
+
+impl ValidationError {
+    // The error as JSON, for the render API.
+    pub fn to_json(&self) -> Value {
+        json!({
+            "type": "validation",
+            "message": self.to_string(),
+            "selector": self.selector,
+        })
+    }
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            ValidationErrorKind::MissingField => {
+                write!(f, "{}: missing required field", self.selector)
+            }
+            ValidationErrorKind::WrongType { expected, found } => {
+                write!(
+                    f,
+                    "{}: expected {}, found {}",
+                    self.selector, expected, found
+                )
+            }
+            ValidationErrorKind::UnknownKey => {
+                write!(
+                    f,
+                    "{}: unknown key, not described by the schema",
+                    self.selector
+                )
+            }
+        }
+    }
+}
+
+/*
+   @param source: &str
+   @return Result<Vec<SchemaField>, String>
+
+   Parses the JSON body of an `slk-dataschema` section. Fields can be given either as an
+   array or as an object keyed by selector, at the top level as well as in nested schemas.
+*/
+pub fn parse_schema(source: &str) -> Result<Vec<SchemaField>, String> {
+    let value: Value = serde_json::from_str(source)
+        .map_err(|error| format!("Slabkit error: couldn't parse slk-dataschema: {}", error))?;
+    fields_from_value(&value)
+}
+
+fn fields_from_value(value: &Value) -> Result<Vec<SchemaField>, String> {
+    match value {
+        Value::Array(fields) => fields
+            .iter()
+            .map(|field| field_from_value(field, None))
+            .collect(),
+        Value::Object(fields) => fields
+            .iter()
+            .map(|(key, field)| field_from_value(field, Some(key)))
+            .collect(),
+        _ => {
+            Err("Slabkit error: slk-dataschema must be an array or an object of fields".to_string())
+        }
+    }
+}
+
+fn field_from_value(value: &Value, key: Option<&str>) -> Result<SchemaField, String> {
+    let field = match value {
+        Value::Object(field) => field,
+        _ => return Err(format!("Slabkit error: invalid schema field: {}", value)),
+    };
+
+    let selector = match (get_string(field, "selector"), key) {
+        (Some(selector), _) => selector,
+        (None, Some(key)) => key.to_string(),
+        (None, None) => {
+            return Err(format!(
+                "Slabkit error: schema field without a selector: {}",
+                value
+            ))
+        }
+    };
+    DataPath::parse(&selector).map_err(|error| format!("Slabkit error: {}", error))?;
+
+    let schema = match field.get("schema") {
+        Some(schema) => fields_from_value(schema)?,
+        None => Vec::new(),
+    };
+
+    Ok(SchemaField {
+        title: get_string(field, "title").unwrap_or_else(|| selector.clone()),
+        placeholder: get_string(field, "placeholder"),
+        field_type: FieldType::from_name(&get_string(field, "type").unwrap_or_default()),
+        selector,
+        required: field
+            .get("required")
+            .and_then(Value::as_bool)
+            .unwrap_or(false),
+        schema,
+    })
+}
+
+fn get_string(field: &Map<String, Value>, key: &str) -> Option<String> {
+    field.get(key).and_then(Value::as_str).map(str::to_string)
+}
+
+/*
+   @param fields: &[SchemaField]
+   @param data: &Value
+   @return Result<(), Vec<ValidationError>>
+
+   Checks the render data against the schema and reports every missing required
+   field, value of the wrong type and key that no field describes.
+*/
+pub fn validate_data(fields: &[SchemaField], data: &Value) -> Result<(), Vec<ValidationError>> {
+    let mut errors = Vec::new();
+    validate_fields(fields, data, "", &mut errors);
+    find_unknown_keys(fields, data, "", &[], &mut errors);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+fn validate_fields(
+    fields: &[SchemaField],
+    data: &Value,
+    prefix: &str,
+    errors: &mut Vec<ValidationError>,
+) {
+    for field in fields {
+        let selector = join_path(prefix, &field.selector);
+        let value = match data_path::lookup(data, &field.selector) {
+            Err(_) | Ok(Value::Null) => {
+                if field.required {
+                    errors.push(ValidationError {
+                        selector,
+                        kind: ValidationErrorKind::MissingField,
+                    });
+                }
+                continue;
+            }
+            Ok(value) => value,
+        };
+        if !field.field_type.matches(value) {
+            errors.push(wrong_type(selector, field.field_type.name(), value));
+            continue;
+        }
+        if field.schema.is_empty() {
+            continue;
+        }
+
+        match value {
+            Value::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    let item_selector = format!("{}[{}]", selector, index);
+                    if !item.is_object() {
+                        errors.push(wrong_type(item_selector, "object", item));
+                        continue;
+                    }
+                    validate_fields(&field.schema, item, &item_selector, errors);
+                    find_unknown_keys(&field.schema, item, &item_selector, &[], errors);
+                }
+            }
+            Value::Object(_) => {
+                validate_fields(&field.schema, value, &selector, errors);
+                find_unknown_keys(&field.schema, value, &selector, &[], errors);
+            }
+            _ => {}
+        }
+    }
+}
+
+/*
+   Reports the keys of `data` that are neither a field selector nor on the way to one.
+   `relative` is the path of `data` relative to the scope the selectors belong to, and
+   `selector` its full path, for the errors.
+*/
+fn find_unknown_keys(
+    fields: &[SchemaField],
+    data: &Value,
+    selector: &str,
+    relative: &[Segment],
+    errors: &mut Vec<ValidationError>,
+) {
+    let object = match data {
+        Value::Object(object) => object,
+        _ => return,
+    };
+    let selectors: Vec<DataPath> = fields
+        .iter()
+        .filter_map(|field| DataPath::parse(&field.selector).ok())
+        .collect();
+    for (key, value) in object {
+        let mut path = relative.to_vec();
+        path.push(Segment::Key(key.clone()));
+        let key_selector = join_key(selector, key);
+        if selectors.iter().any(|field| field.segments() == path) {
+            continue;
+        }
+        if selectors
+            .iter()
+            .any(|field| field.segments().starts_with(&path))
+        {
+            find_unknown_keys(fields, value, &key_selector, &path, errors);
+        } else {
+            errors.push(ValidationError {
+                selector: key_selector,
+                kind: ValidationErrorKind::UnknownKey,
+            });
+        }
+    }
+}
+
+fn wrong_type(selector: String, expected: &str, value: &Value) -> ValidationError {
+    let found = type_name(value);
+    ValidationError {
+        selector,
+        kind: ValidationErrorKind::WrongType {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        },
+    }
+}
+
+// The path of `key` in the value at `selector`, quoted when it isn't a plain dotted key.
+fn join_key(selector: &str, key: &str) -> String {
+    let plain =
+        !key.is_empty() && !key.contains(['.', '[', ']', '"', '\'', '\\']) && key.trim() == key;
+    if plain {
+        return join_path(selector, key);
+    }
+    format!(
+        "{}[\"{}\"]",
+        selector,
+        key.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+fn join_path(prefix: &str, selector: &str) -> String {
+    if prefix.is_empty() {
+        selector.to_string()
+    } else {
+        format!("{}.{}", prefix, selector)
+    }
+}
+
+#[cfg(test)]
+mod schema_tests {
+    use serde_json::json;
+
+    use crate::schema::{
+        parse_schema, validate_data, FieldType, ValidationError, ValidationErrorKind,
+    };
+
+    #[test]
+    fn test_parse_nested_schema() {
+        let fields = parse_schema(
+            r#"[
+                { "title": "Title", "type": "string", "selector": "section.title" },
+                { "title": "Names", "type": "array", "selector": "section.people", "schema": {
+                    "name": { "title": "Name", "type": "string", "placeholder": "Enter the name" },
+                    "email": { "type": "email" }
+                } }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(fields.len(), 2);
+        assert_eq!(fields[0].selector, "section.title");
+        assert_eq!(fields[1].field_type, FieldType::Array);
+        assert_eq!(fields[1].schema[0].selector, "name");
+        assert_eq!(
+            fields[1].schema[0].placeholder.as_deref(),
+            Some("Enter the name")
+        );
+        assert_eq!(fields[1].schema[1].title, "email");
+        assert_eq!(
+            fields[1].schema[1].field_type,
+            FieldType::Input("email".to_string())
+        );
+    }
+
+    #[test]
+    fn test_field_without_selector() {
+        assert!(parse_schema(r#"[{ "title": "Title" }]"#).is_err());
+    }
+
+    #[test]
+    fn test_validate_data() {
+        let fields = parse_schema(
+            r#"[
+                { "title": "Title", "type": "string", "selector": "section.title", "required": true },
+                { "title": "Count", "type": "number", "selector": "section.count" },
+                { "title": "People", "type": "array", "selector": "section.people", "schema": [
+                    { "title": "Name", "type": "string", "selector": "name", "required": true }
+                ] }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            validate_data(
+                &fields,
+                &json!({ "section": { "title": "Hero", "people": [{ "name": "Regis" }] } })
+            ),
+            Ok(())
+        );
+
+        let errors = validate_data(
+            &fields,
+            &json!({
+                "section": { "count": "3", "people": [{ "nick": "rex" }, "jackson"] },
+                "extra": 1
+            }),
+        )
+        .unwrap_err();
+        let error = |selector: &str, kind: ValidationErrorKind| ValidationError {
+            selector: selector.to_string(),
+            kind,
+        };
+        let wrong_type = |expected: &str, found: &str| ValidationErrorKind::WrongType {
+            expected: expected.to_string(),
+            found: found.to_string(),
+        };
+        assert_eq!(
+            errors,
+            vec![
+                error("section.title", ValidationErrorKind::MissingField),
+                error("section.count", wrong_type("number", "string")),
+                error("section.people[0].name", ValidationErrorKind::MissingField),
+                error("section.people[0].nick", ValidationErrorKind::UnknownKey),
+                error("section.people[1]", wrong_type("object", "string")),
+                error("extra", ValidationErrorKind::UnknownKey),
+            ]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "section.count: expected number, found string"
+        );
+    }
+
+    #[test]
+    fn test_validate_data_with_path_grammar() {
+        let fields = parse_schema(
+            r#"[
+                { "title": "First name", "type": "string", "selector": "labels[\"first name\"]" },
+                { "title": "Lead", "type": "string", "selector": "people[0].name", "required": true }
+            ]"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            validate_data(
+                &fields,
+                &json!({ "labels": { "first name": "First" }, "people": [{ "name": "Regis" }] })
+            ),
+            Ok(())
+        );
+
+        let errors = validate_data(
+            &fields,
+            &json!({ "labels": { "first name": 1, "a.b": "" }, "people": [] }),
+        )
+        .unwrap_err();
+        let messages: Vec<String> = errors.iter().map(ToString::to_string).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "labels[\"first name\"]: expected string, found number",
+                "people[0].name: missing required field",
+                "labels[\"a.b\"]: unknown key, not described by the schema",
+            ]
+        );
+
+        assert!(parse_schema(r#"[{ "title": "Title", "selector": "people[" }]"#).is_err());
+    }
+}
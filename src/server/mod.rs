@@ -0,0 +1,1016 @@
+use std::convert::Infallible;
+use std::fs;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::StreamExt;
+use warp::http::StatusCode;
+use warp::path::Tail;
+use warp::{Filter, Reply};
+
+use crate::error::{colour_to_html, strip_colour, use_colour};
+use crate::file_ops::list_files;
+use crate::html::escape;
+
+// Reloads the page when the server sends a `reload` event.
+const RELOAD_SCRIPT: &str = r#"<script>
+new EventSource('/__slabkit/events').addEventListener('reload', function () {
+  location.reload();
+});
+</script>"#;
+
+// The largest request body accepted, in bytes.
+const MAX_BODY_SIZE: u64 = 1024 * 1024;
+
+// What is percent-encoded in the segments of template routes: all but unreserved characters.
+const ROUTE_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+// How often watched files are checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_millis(300);
+
+const ERROR_PAGE_STYLE: &str = r#"
+body { margin: 0; background: #1e1e24; color: #e8e8e8; font-family: system-ui, sans-serif; }
+.slk-error-overlay { max-width: 960px; margin: 48px auto; padding: 24px 32px; }
+.slk-error-overlay h1 { color: #ff6b6b; font-size: 20px; }
+.slk-error-overlay pre { padding: 16px; overflow-x: auto; background: #111116; border-radius: 6px;
+  font: 14px/1.5 ui-monospace, monospace; white-space: pre-wrap; }
+.slk-error { color: #ff6b6b; font-weight: bold; }
+.slk-note { color: #6bb5ff; font-weight: bold; }
+.slk-bold { font-weight: bold; }
+"#;
+
+// Posts the editor form's data on every change and shows the rendered page. The
+// `__SLK_…__` markers are replaced by `editor_page`.
+const EDITOR_SCRIPT: &str = r#"<script>
+(function () {
+  var form = document.querySelector('.slk-editor-form form');
+  var preview = document.querySelector('.slk-editor-preview');
+  var timer;
+  function render() {
+    fetch(__SLK_RENDER_URL__, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify(form.slabkitData())
+    })
+      .then(function (response) { return response.text(); })
+      .then(function (html) { preview.srcdoc = html; });
+  }
+  function schedule() {
+    clearTimeout(timer);
+    timer = setTimeout(render, 150);
+  }
+  if (form) {
+    form.addEventListener('input', schedule);
+    form.addEventListener('change', schedule);
+    form.addEventListener('click', function (event) {
+      if (event.target.closest('[data-slk-add], [data-slk-remove]')) {
+        schedule();
+      }
+    });
+  }
+  if (__SLK_WATCH__) {
+    new EventSource('/__slabkit/events').addEventListener('reload', form ? render : function () {
+      location.reload();
+    });
+  }
+})();
+</script>"#;
+
+const EDITOR_PAGE_STYLE: &str = r#"
+body { margin: 0; font-family: system-ui, sans-serif; color: #1e1e24; }
+.slk-editor { display: grid; grid-template-columns: minmax(280px, 1fr) 2fr; height: 100vh; }
+.slk-editor-form { padding: 16px 24px; overflow-y: auto; border-right: 1px solid #ddd; }
+.slk-editor-form .slk-field { display: flex; flex-direction: column; gap: 4px; margin: 12px 0; }
+.slk-editor-preview { width: 100%; height: 100%; border: 0; }
+"#;
+
+const INDEX_PAGE_STYLE: &str = r#"
+body { margin: 0; font-family: system-ui, sans-serif; color: #1e1e24; }
+.slk-index { max-width: 720px; margin: 48px auto; padding: 0 24px; }
+.slk-index dl { display: grid; grid-template-columns: max-content auto; gap: 4px 16px; color: #555; }
+.slk-index dd { margin: 0; }
+.slk-index li { margin: 8px 0; }
+.slk-index code { color: #888; }
+"#;
+
+/*
+   A rendered page and the files it was rendered from: the template, the data file and
+   the included files. `form` is the data entry form of the template's schema, pre-filled
+   with the data the page was rendered with, for the live editor.
+*/
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Page {
+    pub html: String,
+    pub files: Vec<PathBuf>,
+    pub form: Option<String>,
+}
+
+// The page, or the error report of a failed build. Reports may be coloured.
+pub type Build = Result<Page, String>;
+
+// The HTML rendered by the API, or its errors as JSON objects with a `type` and a `message`.
+pub type ApiRender = Result<String, Vec<Value>>;
+
+// Where inline templates of the API are, so their includes resolve from the directory.
+pub const INLINE_TEMPLATE: &str = "<inline>";
+
+/*
+   @param address: SocketAddr
+   @param build: Fn(Option<Value>) -> Build
+   @param watch: bool
+
+   Serves the page returned by `build`. When the build fails, the error report is
+   served instead as an error page, and the page is built again on the next request.
+   With `watch`, the files of the page are checked for changes, the page is rebuilt
+   when one changes (or until a failed build succeeds) and open browsers reload through
+   server-sent events on `/__slabkit/events`.
+
+   The live editor at `/__slabkit/editor` shows the page next to its data form and
+   renders it again with the form's data, posted as JSON to `/__slabkit/render`, on
+   every change. `build` gets the posted data, which replaces the data file, or None.
+*/
+pub async fn serve<F>(address: SocketAddr, build: F, watch: bool)
+where
+    F: Fn(Option<Value>) -> Build + Send + Sync + 'static,
+{
+    let first_build = build(None);
+    if let Err(report) = &first_build {
+        print_report(report);
+    }
+    let state = Arc::new(State {
+        build,
+        last: RwLock::new(first_build),
+        watch,
+    });
+    let (reload, _) = broadcast::channel::<()>(16);
+
+    if watch {
+        tokio::spawn(watch_files(state.clone(), reload.clone()));
+    }
+
+    let page_state = state.clone();
+    let page_route = warp::path::end().and_then(move || {
+        let state = page_state.clone();
+        html_reply(move || state.response())
+    });
+    let editor_state = state.clone();
+    let editor_route = warp::path!("__slabkit" / "editor")
+        .and(warp::get())
+        .and_then(move || {
+            let state = editor_state.clone();
+            html_reply(move || {
+                let editor = editor_page(&(state.build)(None), "/__slabkit/render", state.watch);
+                (editor, StatusCode::OK)
+            })
+        });
+    let render_route = warp::path!("__slabkit" / "render")
+        .and(warp::post())
+        .and(json_body())
+        .and_then(move |data: Value| {
+            let state = state.clone();
+            html_reply(move || preview_response((state.build)(Some(data))))
+        });
+
+    println!("Server running at http://{}", address);
+    println!("Live editor at http://{}/__slabkit/editor", address);
+    warp::serve(
+        page_route
+            .or(editor_route)
+            .or(render_route)
+            .or(events_route(reload)),
+    )
+    .run(address)
+    .await;
+}
+
+/*
+   @param address: SocketAddr
+   @param dir: PathBuf
+   @param build: Fn(&Path, Option<Value>) -> Build
+   @param watch: bool
+
+   Serves every template of `dir` at its path without the `.html` extension (so
+   `partials/footer.html` is at `/partials/footer`), an index of the templates at `/`
+   and any other file of the directory as is. Pages are built on each request with
+   `build`. With `watch`, open browsers reload when a file of the directory changes.
+   Each template has a live editor at `/__slabkit/editor/<path>`, rendering through
+   `/__slabkit/render/<path>`, as with `serve`.
+*/
+pub async fn serve_dir<F>(address: SocketAddr, dir: PathBuf, build: F, watch: bool)
+where
+    F: Fn(&Path, Option<Value>) -> Build + Send + Sync + 'static,
+{
+    let site = Arc::new(Site {
+        dir: dir.clone(),
+        build,
+        watch,
+    });
+    let (reload, _) = broadcast::channel::<()>(16);
+
+    if watch {
+        tokio::spawn(watch_dir(dir.clone(), reload.clone()));
+    }
+
+    let index_site = site.clone();
+    let index_route = warp::path::end().and_then(move || {
+        let site = index_site.clone();
+        html_reply(move || (site.index(), StatusCode::OK))
+    });
+    let editor_site = site.clone();
+    let editor_route = warp::path!("__slabkit" / "editor" / ..)
+        .and(warp::get())
+        .and(warp::path::tail())
+        .and_then(move |tail: Tail| {
+            let site = editor_site.clone();
+            template_reply(site.clone(), tail, move |template| {
+                (site.editor(&template), StatusCode::OK)
+            })
+        });
+    let render_site = site.clone();
+    let render_route = warp::path!("__slabkit" / "render" / ..)
+        .and(warp::post())
+        .and(warp::path::tail())
+        .and(json_body())
+        .and_then(move |tail: Tail, data: Value| {
+            let site = render_site.clone();
+            template_reply(site.clone(), tail, move |template| {
+                site.preview(&template, data)
+            })
+        });
+    let pages_route = warp::path::tail().and_then(move |tail: Tail| {
+        let site = site.clone();
+        template_reply(site.clone(), tail, move |template| site.page(&template))
+    });
+
+    println!("Serving {} at http://{}", dir.display(), address);
+    warp::serve(
+        index_route
+            .or(editor_route)
+            .or(render_route)
+            .or(events_route(reload))
+            .or(pages_route)
+            .or(warp::fs::dir(dir)),
+    )
+    .run(address)
+    .await;
+}
+
+/*
+   @param address: SocketAddr
+   @param dir: PathBuf
+   @param render: Fn(&str, &Path, Option<Value>) -> ApiRender
+
+   Serves the render API. `POST /render` takes a JSON object with either the `template`
+   to render, the path of a template of `dir` with or without `.html`, or an inline
+   template `source`, and the render `data`. `render` gets the template source, its path
+   (`INLINE_TEMPLATE` in `dir` for inline templates) and the data, and the response is
+   the rendered HTML or `{"errors": [...]}` with a 4xx or 500 status. `render` must keep
+   includes inside `dir`, since anyone who can reach the server picks the templates.
+*/
+pub async fn serve_api<F>(address: SocketAddr, dir: PathBuf, render: F)
+where
+    F: Fn(&str, &Path, Option<Value>) -> ApiRender + Send + Sync + 'static,
+{
+    let render = Arc::new(render);
+    let api_dir = Arc::new(dir.clone());
+    let render_route = warp::path!("render")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(MAX_BODY_SIZE))
+        .and(warp::body::bytes())
+        .and_then(move |body: warp::hyper::body::Bytes| {
+            let (dir, render) = (api_dir.clone(), render.clone());
+            async move {
+                let response = blocking(move || api_response(&dir, &body, render.as_ref()))
+                    .await
+                    .unwrap_or_else(|report| {
+                        Err(api_error(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "render",
+                            &report,
+                        ))
+                    });
+                Ok::<_, Infallible>(match response {
+                    Ok(html) => warp::reply::with_status(warp::reply::html(html), StatusCode::OK)
+                        .into_response(),
+                    Err((status, errors)) => warp::reply::with_status(
+                        warp::reply::json(&json!({ "errors": errors })),
+                        status,
+                    )
+                    .into_response(),
+                })
+            }
+        });
+
+    println!(
+        "Render API for {} at http://{}/render",
+        dir.display(),
+        address
+    );
+    warp::serve(render_route).run(address).await;
+}
+
+fn api_response<F>(dir: &Path, body: &[u8], render: &F) -> Result<String, (StatusCode, Vec<Value>)>
+where
+    F: Fn(&str, &Path, Option<Value>) -> ApiRender,
+{
+    let request: Value = serde_json::from_slice(body)
+        .map_err(|error| bad_request(&format!("the body isn't valid JSON: {}", error)))?;
+    let data = request.get("data").cloned();
+    let (source, path) =
+        match (request.get("template"), request.get("source")) {
+            (Some(Value::String(name)), None) => {
+                let template = find_template(dir, name).ok_or_else(|| {
+                    api_error(
+                        StatusCode::NOT_FOUND,
+                        "not_found",
+                        &format!("no template `{}`", name),
+                    )
+                })?;
+                let path = dir.join(&template);
+                let source = fs::read_to_string(&path).map_err(|error| {
+                    api_error(
+                        StatusCode::INTERNAL_SERVER_ERROR,
+                        "io",
+                        &format!("couldn't read {}: {}", path_string(&template), error),
+                    )
+                })?;
+                (source, path)
+            }
+            (None, Some(Value::String(source))) => (source.clone(), dir.join(INLINE_TEMPLATE)),
+            _ => return Err(bad_request(
+                "expected an object with either a `template` name or a template `source` string",
+            )),
+        };
+
+    render(&source, &path, data).map_err(|errors| (StatusCode::UNPROCESSABLE_ENTITY, errors))
+}
+
+fn bad_request(message: &str) -> (StatusCode, Vec<Value>) {
+    api_error(StatusCode::BAD_REQUEST, "request", message)
+}
+
+fn api_error(status: StatusCode, error_type: &str, message: &str) -> (StatusCode, Vec<Value>) {
+    (
+        status,
+        vec![json!({ "type": error_type, "message": message })],
+    )
+}
+
+/*
+   Runs `work` on a thread for blocking work, since building pages reads files and can take
+   a while, and the workers serving requests must not wait for it. A panic of `work` is
+   returned as an error report.
+*/
+async fn blocking<T, W>(work: W) -> Result<T, String>
+where
+    T: Send + 'static,
+    W: FnOnce() -> T + Send + 'static,
+{
+    tokio::task::spawn_blocking(work)
+        .await
+        .map_err(|error| format!("Slabkit error: the build failed: {}", error))
+}
+
+// The HTML page built by `work`, off the workers serving requests.
+async fn html_reply<W>(work: W) -> Result<warp::reply::Response, Infallible>
+where
+    W: FnOnce() -> (String, StatusCode) + Send + 'static,
+{
+    let (html, status) = blocking(work).await.unwrap_or_else(failed_build);
+    Ok(warp::reply::with_status(warp::reply::html(html), status).into_response())
+}
+
+// The HTML page built by `work` for the template of `site` at the route `tail`, if any.
+async fn template_reply<F, W>(
+    site: Arc<Site<F>>,
+    tail: Tail,
+    work: W,
+) -> Result<warp::reply::Response, warp::Rejection>
+where
+    F: Fn(&Path, Option<Value>) -> Build + Send + Sync + 'static,
+    W: FnOnce(PathBuf) -> (String, StatusCode) + Send + 'static,
+{
+    let route = tail.as_str().to_string();
+    let (html, status) = match blocking(move || site.template(&route).map(work)).await {
+        Ok(Some(page)) => page,
+        Ok(None) => return Err(warp::reject::not_found()),
+        Err(report) => failed_build(report),
+    };
+    Ok(warp::reply::with_status(warp::reply::html(html), status).into_response())
+}
+
+fn failed_build(report: String) -> (String, StatusCode) {
+    print_report(&report);
+    (error_page(&report), StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// Sends a `reload` event to the page whenever something is sent on `reload`.
+fn events_route(
+    reload: broadcast::Sender<()>,
+) -> impl Filter<Extract = (impl warp::Reply,), Error = warp::Rejection> + Clone {
+    warp::path!("__slabkit" / "events").map(move || {
+        let events = BroadcastStream::new(reload.subscribe())
+            .filter_map(|message| message.ok())
+            .map(|_| Ok::<_, Infallible>(warp::sse::Event::default().event("reload").data("")));
+        warp::sse::reply(warp::sse::keep_alive().stream(events))
+    })
+}
+
+// The JSON data posted by the live editor.
+fn json_body() -> impl Filter<Extract = (Value,), Error = warp::Rejection> + Clone {
+    warp::body::content_length_limit(MAX_BODY_SIZE).and(warp::body::json())
+}
+
+struct Site<F> {
+    dir: PathBuf,
+    build: F,
+    watch: bool,
+}
+
+impl<F: Fn(&Path, Option<Value>) -> Build> Site<F> {
+    fn templates(&self) -> Vec<PathBuf> {
+        templates(&self.dir)
+    }
+
+    // The template at a percent-encoded route, never outside of the directory.
+    fn template(&self, route: &str) -> Option<PathBuf> {
+        let route = percent_decode_str(route).decode_utf8().ok()?;
+        if route.split(['/', '\\']).any(|segment| segment == "..") {
+            return None;
+        }
+        find_template(&self.dir, &route)
+    }
+
+    fn page(&self, template: &Path) -> (String, StatusCode) {
+        match (self.build)(&self.dir.join(template), None) {
+            Ok(page) => (with_reload(page.html, self.watch), StatusCode::OK),
+            Err(report) => {
+                print_report(&report);
+                (
+                    with_reload(error_page(&report), self.watch),
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                )
+            }
+        }
+    }
+
+    fn editor(&self, template: &Path) -> String {
+        editor_page(
+            &(self.build)(&self.dir.join(template), None),
+            &format!("/__slabkit/render/{}", route_url(template)),
+            self.watch,
+        )
+    }
+
+    fn preview(&self, template: &Path, data: Value) -> (String, StatusCode) {
+        preview_response((self.build)(&self.dir.join(template), Some(data)))
+    }
+
+    fn index(&self) -> String {
+        let info = fs::read_to_string(self.dir.join(".lepper").join("_info.json"))
+            .ok()
+            .and_then(|info| serde_json::from_str(&info).ok())
+            .unwrap_or_default();
+        with_reload(index_page(&info, &self.templates()), self.watch)
+    }
+}
+
+async fn watch_dir(dir: PathBuf, reload: broadcast::Sender<()>) {
+    let mut modified = None;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let scanned = dir.clone();
+        let current = match blocking(move || dir_modification_times(&scanned)).await {
+            Ok(current) => current,
+            Err(report) => {
+                print_report(&report);
+                continue;
+            }
+        };
+        if modified
+            .as_ref()
+            .is_some_and(|modified| *modified != current)
+        {
+            println!("Files changed, reloading");
+            // Nobody listening is fine.
+            let _ = reload.send(());
+        }
+        modified = Some(current);
+    }
+}
+
+// The templates of `dir`, relative to it. Looked up on each request, so new templates
+// show up without a restart.
+fn templates(dir: &Path) -> Vec<PathBuf> {
+    list_files(dir)
+        .into_iter()
+        .filter(|file| {
+            file.extension()
+                .is_some_and(|extension| extension == "html")
+        })
+        .collect()
+}
+
+// The template of `dir` at `route`, given with or without its extension.
+fn find_template(dir: &Path, route: &str) -> Option<PathBuf> {
+    templates(dir)
+        .into_iter()
+        .find(|template| route_of(template) == route || path_string(template) == route)
+}
+
+fn dir_modification_times(dir: &Path) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let files = list_files(dir);
+    let modified = modification_times(&files.iter().map(|file| dir.join(file)).collect::<Vec<_>>());
+    files.into_iter().zip(modified).collect()
+}
+
+// The route of a template: its path with `/` separators and without the extension.
+fn route_of(template: &Path) -> String {
+    path_string(&template.with_extension(""))
+}
+
+// The route of a template as it is written in URLs, each segment percent-encoded.
+fn route_url(template: &Path) -> String {
+    route_of(template)
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, ROUTE_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().replace('\\', "/")
+}
+
+/*
+   @param info: &Value
+   @param templates: &[PathBuf]
+   @return String
+
+   The index of a served directory: the project metadata of `.lepper/_info.json` (its
+   `name` as the title, the other fields listed) and a link to every template.
+*/
+pub fn index_page(info: &Value, templates: &[PathBuf]) -> String {
+    let title = info
+        .get("name")
+        .and_then(Value::as_str)
+        .unwrap_or("Templates");
+    let metadata: String = info
+        .as_object()
+        .map(|info| {
+            info.iter()
+                .filter(|(key, _)| *key != "name")
+                .map(|(key, value)| {
+                    let value = match value {
+                        Value::String(value) => value.clone(),
+                        value => value.to_string(),
+                    };
+                    format!("<dt>{}</dt><dd>{}</dd>\n", escape(key), escape(&value))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    let links: String = templates
+        .iter()
+        .map(|template| {
+            format!(
+                "<li><a href=\"/{url}\">{route}</a> <code>{file}</code> \
+                 <a href=\"/__slabkit/editor/{url}\">edit</a></li>\n",
+                url = escape(&route_url(template)),
+                route = escape(&route_of(template)),
+                file = escape(&path_string(template))
+            )
+        })
+        .collect();
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>{style}</style>
+</head>
+<body>
+<main class="slk-index">
+<h1>{title}</h1>
+<dl>
+{metadata}</dl>
+<ul>
+{links}</ul>
+</main>
+</body>
+</html>
+"#,
+        title = escape(title),
+        style = INDEX_PAGE_STYLE,
+        metadata = metadata,
+        links = links
+    )
+}
+
+// Files a page was built from, with their modification times.
+type Watched = (Vec<PathBuf>, Vec<Option<SystemTime>>);
+
+struct State<F> {
+    build: F,
+    last: RwLock<Build>,
+    watch: bool,
+}
+
+impl<F: Fn(Option<Value>) -> Build> State<F> {
+    fn response(&self) -> (String, StatusCode) {
+        // Without a watcher, a failed build is retried when the page is requested again.
+        if !self.watch && self.last().is_err() {
+            self.rebuild();
+        }
+        match self.last() {
+            Ok(page) => (self.with_reload(page.html), StatusCode::OK),
+            Err(report) => (
+                self.with_reload(error_page(&report)),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+        }
+    }
+
+    fn last(&self) -> Build {
+        self.last
+            .read()
+            .map(|last| last.clone())
+            .unwrap_or_else(|_| Err("Slabkit error: the server state is unavailable".to_string()))
+    }
+
+    // Builds the page again and returns whether the result changed.
+    fn rebuild(&self) -> bool {
+        let build = (self.build)(None);
+        if build == self.last() {
+            return false;
+        }
+        match &build {
+            Ok(_) => println!("Rebuilt the page"),
+            Err(report) => print_report(report),
+        }
+        if let Ok(mut last) = self.last.write() {
+            *last = build;
+        }
+        true
+    }
+
+    /*
+       Rebuilds the page when one of the `watched` files it was built from changed, the
+       files of the last build when `None`, and returns whether the page changed and the
+       files to watch next.
+    */
+    fn check(&self, watched: Option<Watched>) -> (bool, Option<Watched>) {
+        let (files, modified) = watched.unwrap_or_else(|| {
+            let files = self.last().map(|page| page.files).unwrap_or_default();
+            let modified = modification_times(&files);
+            (files, modified)
+        });
+        // A failed build may depend on files we don't know about yet, so it's retried
+        // until it changes.
+        if self.last().is_ok() && modification_times(&files) == modified {
+            return (false, Some((files, modified)));
+        }
+
+        let changed = self.rebuild();
+        let files = self.last().map(|page| page.files).unwrap_or(files);
+        let modified = modification_times(&files);
+        (changed, Some((files, modified)))
+    }
+
+    fn with_reload(&self, html: String) -> String {
+        with_reload(html, self.watch)
+    }
+}
+
+fn with_reload(html: String, watch: bool) -> String {
+    if watch {
+        inject_reload_script(&html)
+    } else {
+        html
+    }
+}
+
+async fn watch_files<F>(state: Arc<State<F>>, reload: broadcast::Sender<()>)
+where
+    F: Fn(Option<Value>) -> Build + Send + Sync + 'static,
+{
+    let mut watched = None;
+    let mut interval = tokio::time::interval(WATCH_INTERVAL);
+    loop {
+        interval.tick().await;
+        let state = state.clone();
+        let (changed, files) = match blocking(move || state.check(watched)).await {
+            Ok(check) => check,
+            Err(report) => {
+                print_report(&report);
+                (false, None)
+            }
+        };
+        watched = files;
+        if changed {
+            // Nobody listening is fine.
+            let _ = reload.send(());
+        }
+    }
+}
+
+fn print_report(report: &str) {
+    if use_colour() {
+        eprintln!("{}", report.trim_end());
+    } else {
+        eprintln!("{}", strip_colour(report).trim_end());
+    }
+}
+
+/*
+   @param report: &str
+   @return String
+
+   The page served in place of a page that failed to build, showing the error report
+   with its source snippets.
+*/
+pub fn error_page(report: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Slabkit error</title>
+<style>{}</style>
+</head>
+<body>
+<div class="slk-error-overlay">
+<h1>The page failed to build</h1>
+<pre>{}</pre>
+<p>Fix the template and the page will update.</p>
+</div>
+</body>
+</html>
+"#,
+        ERROR_PAGE_STYLE,
+        colour_to_html(report.trim_end())
+    )
+}
+
+/*
+   @param build: &Build
+   @param render_url: &str
+   @param watch: bool
+   @return String
+
+   The live editor of a page: its data form next to a preview of the page, rendered
+   again on every change of the form by posting the form's data to `render_url`. A
+   template without a schema only gets the preview.
+*/
+pub fn editor_page(build: &Build, render_url: &str, watch: bool) -> String {
+    let page = match build {
+        Ok(page) => page,
+        Err(report) => {
+            print_report(report);
+            return with_reload(error_page(report), watch);
+        }
+    };
+    let form = page.form.clone().unwrap_or_else(|| {
+        "<p>This template has no <code>slk-dataschema</code> section to edit.</p>".to_string()
+    });
+    let script = EDITOR_SCRIPT
+        .replace(
+            "__SLK_RENDER_URL__",
+            &serde_json::to_string(render_url).unwrap_or_default(),
+        )
+        .replace("__SLK_WATCH__", &watch.to_string());
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>Slabkit editor</title>
+<style>{}</style>
+</head>
+<body>
+<div class="slk-editor">
+<div class="slk-editor-form">
+{}
+</div>
+<iframe class="slk-editor-preview" srcdoc="{}"></iframe>
+</div>
+{}
+</body>
+</html>
+"#,
+        EDITOR_PAGE_STYLE,
+        form,
+        escape(&page.html),
+        script
+    )
+}
+
+// The page rendered with the editor's data, or its error page.
+fn preview_response(build: Build) -> (String, StatusCode) {
+    match build {
+        Ok(page) => (page.html, StatusCode::OK),
+        Err(report) => {
+            print_report(&report);
+            (error_page(&report), StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/*
+   @param html: &str
+   @return String
+
+   Adds the live reload script before `</body>`, or at the end of a page without one.
+*/
+pub fn inject_reload_script(html: &str) -> String {
+    match html.rfind("</body>") {
+        Some(index) => format!("{}{}{}", &html[..index], RELOAD_SCRIPT, &html[index..]),
+        None => format!("{}{}", html, RELOAD_SCRIPT),
+    }
+}
+
+// Missing files read as None, so creating one counts as a change.
+fn modification_times(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files
+        .iter()
+        .map(|file| {
+            fs::metadata(file)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod server_tests {
+    use std::path::{Path, PathBuf};
+
+    use serde_json::{json, Value};
+    use warp::http::StatusCode;
+
+    use crate::server::{
+        api_response, editor_page, error_page, index_page, inject_reload_script, ApiRender, Build,
+        Page, Site, INLINE_TEMPLATE, RELOAD_SCRIPT,
+    };
+
+    #[test]
+    fn test_inject_reload_script() {
+        assert_eq!(
+            inject_reload_script("<html><body><p>Hi</p></body></html>"),
+            format!("<html><body><p>Hi</p>{}</body></html>", RELOAD_SCRIPT)
+        );
+        assert_eq!(
+            inject_reload_script("<p>Hi</p>"),
+            format!("<p>Hi</p>{}", RELOAD_SCRIPT)
+        );
+    }
+
+    #[test]
+    fn test_error_page() {
+        let page = error_page("\x1b[1;31merror\x1b[0m: unclosed tag `<div>`\n");
+        assert!(page.contains(
+            r#"<pre><span class="slk-error">error</span>: unclosed tag `&lt;div&gt;`</pre>"#
+        ));
+        assert_eq!(
+            inject_reload_script(&page).matches("EventSource").count(),
+            1
+        );
+    }
+
+    #[test]
+    fn test_index_page() {
+        let page = index_page(
+            &json!({ "name": "Slabit", "version": "0.0.1", "author": "Regis <R>" }),
+            &[
+                PathBuf::from("hero.html"),
+                PathBuf::from("partials/footer.html"),
+                PathBuf::from("my page.html"),
+                PathBuf::from("café.html"),
+            ],
+        );
+        assert!(page.contains("<h1>Slabit</h1>"));
+        assert!(page.contains("<dt>version</dt><dd>0.0.1</dd>"));
+        assert!(page.contains("<dt>author</dt><dd>Regis &lt;R&gt;</dd>"));
+        assert!(page.contains(
+            r#"<li><a href="/hero">hero</a> <code>hero.html</code> <a href="/__slabkit/editor/hero">edit</a></li>"#
+        ));
+        assert!(page.contains(r#"<a href="/partials/footer">partials/footer</a>"#));
+        assert!(page.contains(r#"<a href="/__slabkit/editor/partials/footer">edit</a>"#));
+        assert!(page.contains(
+            r#"<li><a href="/my%20page">my page</a> <code>my page.html</code> <a href="/__slabkit/editor/my%20page">edit</a></li>"#
+        ));
+        assert!(page.contains(r#"<a href="/caf%C3%A9">café</a>"#));
+    }
+
+    #[test]
+    fn test_site_routes() {
+        let dir = std::env::temp_dir().join("slabkit_site_routes");
+        std::fs::create_dir_all(dir.join("pages")).unwrap();
+        for file in ["my page.html", "café.html", "pages/hero.html"] {
+            std::fs::write(dir.join(file), "<p>Hi</p>").unwrap();
+        }
+        let site = Site {
+            dir: dir.clone(),
+            build: |_: &Path, _: Option<Value>| -> Build { Err(String::new()) },
+            watch: false,
+        };
+
+        assert_eq!(
+            site.template("my%20page"),
+            Some(PathBuf::from("my page.html"))
+        );
+        assert_eq!(
+            site.template("caf%C3%A9.html"),
+            Some(PathBuf::from("café.html"))
+        );
+        assert_eq!(
+            site.template("pages/hero"),
+            Some(PathBuf::from("pages/hero.html"))
+        );
+        assert_eq!(site.template("pages/%2E%2E/pages/hero"), None);
+        assert_eq!(site.template("caf%C3"), None);
+    }
+
+    #[test]
+    fn test_editor_page() {
+        let page = Page {
+            html: r#"<h1 class="title">Hi</h1>"#.to_string(),
+            files: vec![],
+            form: Some(r#"<form class="slk-form"></form>"#.to_string()),
+        };
+        let editor = editor_page(&Ok(page), "/__slabkit/render/hero", false);
+        assert!(editor.contains(r#"<form class="slk-form"></form>"#));
+        assert!(editor.contains(r#"srcdoc="&lt;h1 class=&quot;title&quot;&gt;Hi&lt;/h1&gt;""#));
+        assert!(editor.contains(r#"fetch("/__slabkit/render/hero", {"#));
+        assert!(editor.contains("if (false) {"));
+
+        let page = Page {
+            html: String::new(),
+            files: vec![],
+            form: None,
+        };
+        let editor = editor_page(&Ok(page), "/__slabkit/render/WATCHLIST", true);
+        assert!(editor.contains(r#"fetch("/__slabkit/render/WATCHLIST", {"#));
+        assert!(editor.contains("if (true) {"));
+    }
+
+    #[test]
+    fn test_api_response() {
+        let dir = std::env::temp_dir().join("slabkit_api");
+        std::fs::create_dir_all(dir.join("partials")).unwrap();
+        std::fs::write(dir.join("partials/card.html"), "<p>Card</p>").unwrap();
+        let render = |source: &str, path: &Path, data: Option<Value>| -> ApiRender {
+            Ok(format!(
+                "{} {} {}",
+                source,
+                path.display(),
+                data.unwrap_or_default()
+            ))
+        };
+        let request = |body: &str| api_response(&dir, body.as_bytes(), &render);
+
+        assert_eq!(
+            request(r#"{"template": "partials/card", "data": {"a": 1}}"#),
+            Ok(format!(
+                "<p>Card</p> {} {{\"a\":1}}",
+                dir.join("partials/card.html").display()
+            ))
+        );
+        assert_eq!(
+            request(r#"{"source": "<p>Inline</p>"}"#),
+            Ok(format!(
+                "<p>Inline</p> {} null",
+                dir.join(INLINE_TEMPLATE).display()
+            ))
+        );
+        assert_eq!(
+            request(r#"{"template": "missing"}"#).unwrap_err().0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            request(r#"{"template": "card", "source": "<p></p>"}"#).unwrap_err(),
+            (
+                StatusCode::BAD_REQUEST,
+                vec![json!({
+                    "type": "request",
+                    "message": "expected an object with either a `template` name or a template `source` string",
+                })]
+            )
+        );
+        assert_eq!(request("{").unwrap_err().0, StatusCode::BAD_REQUEST);
+    }
+}
@@ -0,0 +1,209 @@
+use serde_json::Value;
+
+use crate::error::SlabkitError;
+use crate::parser::{HtmlElement, Node, Parser};
+use crate::schema::{parse_schema, SchemaField};
+
+pub const SECTION_TAG: &str = "slk-section";
+pub const PREVIEW_DATA_TAG: &str = "slk-previewdata";
+pub const DATA_SCHEMA_TAG: &str = "slk-dataschema";
+
+/*
+   A parsed slabkit file.
+
+   `nodes` holds every root node in source order. The top-level sections are exposed
+   as typed fields and may appear in any order; each of them appears at most once.
+*/
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub nodes: Vec<Node>,
+    pub section: Option<HtmlElement>,
+    pub preview_data: Option<Value>,
+    pub schema: Option<Vec<SchemaField>>,
+}
+
+impl Template {
+    pub fn parse(source: String) -> Result<Self, SlabkitError> {
+        let nodes = Parser::new(source).parse_nodes()?;
+        let mut template = Template {
+            nodes: Vec::new(),
+            section: None,
+            preview_data: None,
+            schema: None,
+        };
+
+        for node in nodes.iter() {
+            let elt = match node {
+                Node::Element(elt) => elt,
+                Node::Text(..) => continue,
+            };
+            match elt.tag.as_str() {
+                SECTION_TAG => {
+                    ensure_single(&template.section, SECTION_TAG)?;
+                    template.section = Some(elt.clone());
+                }
+                PREVIEW_DATA_TAG => {
+                    ensure_single(&template.preview_data, PREVIEW_DATA_TAG)?;
+                    let source = text_content(elt);
+                    template.preview_data =
+                        Some(serde_json::from_str(&source).map_err(|error| {
+                            SlabkitError::Template(format!(
+                                "Slabkit error: couldn't parse slk-previewdata: {}",
+                                error
+                            ))
+                        })?);
+                }
+                DATA_SCHEMA_TAG => {
+                    ensure_single(&template.schema, DATA_SCHEMA_TAG)?;
+                    template.schema =
+                        Some(parse_schema(&text_content(elt)).map_err(SlabkitError::Template)?);
+                }
+                _ => {}
+            }
+        }
+
+        template.nodes = nodes;
+        Ok(template)
+    }
+
+    /*
+       The data to render with: the preview data, with `data` deep merged over it when
+       given, so a partial data file still fills every placeholder of the preview.
+    */
+    pub fn render_data(&self, data: Option<Value>) -> Value {
+        let mut render_data = self.preview_data.clone().unwrap_or(Value::Null);
+        // A missing or unparsable data file reads as null.
+        if let Some(data) = data.filter(|data| !data.is_null()) {
+            merge_data(&mut render_data, data);
+        }
+        render_data
+    }
+
+    /*
+       The renderable content: the children of `slk-section`, or every root node other
+       than the data sections when the file has no `slk-section`.
+    */
+    pub fn body(&self) -> Vec<Box<Node>> {
+        match &self.section {
+            Some(section) => section.children.clone(),
+            None => self
+                .nodes
+                .iter()
+                .filter(|node| match node {
+                    Node::Element(elt) => elt.tag != PREVIEW_DATA_TAG && elt.tag != DATA_SCHEMA_TAG,
+                    Node::Text(..) => true,
+                })
+                .cloned()
+                .map(Box::new)
+                .collect(),
+        }
+    }
+}
+
+/*
+   @param base: &mut Value
+   @param overlay: Value
+
+   Deep merges `overlay` into `base`: objects are merged key by key, any other value
+   (arrays included) replaces the one in `base`.
+*/
+pub fn merge_data(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Object(base), Value::Object(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_data(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+fn ensure_single<T>(section: &Option<T>, tag: &str) -> Result<(), SlabkitError> {
+    match section {
+        Some(_) => Err(SlabkitError::Template(format!(
+            "Slabkit error: <{}> can only appear once in a template",
+            tag
+        ))),
+        None => Ok(()),
+    }
+}
+
+fn text_content(elt: &HtmlElement) -> String {
+    elt.children
+        .iter()
+        .map(|child| child.node_to_html())
+        .collect()
+}
+
+#[cfg(test)]
+mod template_tests {
+    use serde_json::json;
+
+    use crate::template::{merge_data, Template};
+
+    #[test]
+    fn test_sections_in_any_order() {
+        let source = r#"
+<slk-dataschema>[{ "title": "Title", "selector": "title" }]</slk-dataschema>
+<slk-previewdata>{ "title": "<Hero>" }</slk-previewdata>
+<slk-section>
+  <h1>!{title}!</h1>
+</slk-section>
+"#;
+        let template = Template::parse(source.to_string()).unwrap();
+
+        assert_eq!(template.nodes.len(), 3);
+        assert_eq!(template.preview_data, Some(json!({ "title": "<Hero>" })));
+        assert_eq!(template.schema.as_ref().unwrap()[0].selector, "title");
+        let body: String = template.body().iter().map(|n| n.node_to_html()).collect();
+        assert_eq!(body, "\n  <h1>!{title}!</h1>\n");
+    }
+
+    #[test]
+    fn test_without_section_and_duplicates() {
+        let template = Template::parse("<p>One</p>\n<p>Two</p>".to_string()).unwrap();
+        assert!(template.section.is_none());
+        assert_eq!(template.body().len(), 2);
+
+        let duplicated = "<slk-section></slk-section><slk-section></slk-section>";
+        assert!(Template::parse(duplicated.to_string()).is_err());
+    }
+
+    #[test]
+    fn test_render_data_merges_over_preview() {
+        let source = r#"<slk-previewdata>{
+            "title": "Preview",
+            "section": { "description": "Preview description", "people": [1, 2] }
+        }</slk-previewdata>"#;
+        let template = Template::parse(source.to_string()).unwrap();
+
+        assert_eq!(
+            template.render_data(None),
+            template.preview_data.clone().unwrap()
+        );
+        assert_eq!(
+            template.render_data(Some(json!({ "section": { "people": [3] }, "extra": true }))),
+            json!({
+                "title": "Preview",
+                "section": { "description": "Preview description", "people": [3] },
+                "extra": true
+            })
+        );
+    }
+
+    #[test]
+    fn test_merge_replaces_non_objects() {
+        let mut base = json!({ "a": { "b": 1 } });
+        merge_data(&mut base, json!({ "a": "text" }));
+        assert_eq!(base, json!({ "a": "text" }));
+
+        let mut base = json!(null);
+        merge_data(&mut base, json!({ "a": 1 }));
+        assert_eq!(base, json!({ "a": 1 }));
+    }
+}
@@ -1,63 +1,34 @@
 
-use crate::{file_ops, logger::Logger};
+use std::path::Path;
 
-pub fn get_action( action : &Option<String> ) -> String {
-    let logger = Logger::new(
-        "INFO".to_string(),
-        "Getting action".to_string(),
-    );
-    match action {
-        Some(action) => {
-            if action == "dev" || action == "compile" {
-                action.to_string()
-            } else {
-                logger.error("Invalid action");
-                String::from("dev")
-            }
-        },
-        None => String::from("dev")
-    }
+use serde_json::Value;
 
-}
+use crate::file_ops;
 
-pub fn get_template( template : &Option<String> ) -> String {
-    let logger = Logger::new(
-        "INFO".to_string(),
-        "Getting template".to_string(),
-    );
-    match template {
-        Some(template) => {
-            file_ops::get_file(template)
-        },
-        None => {
-            logger.error("No template provided");
-            String::from("template.html")
-        }
-    }
+pub fn get_template( template : &str ) -> Result<String, String> {
+    file_ops::read_file(Path::new(template))
+        .map_err(|error| format!("Slabkit error: couldn't read the template '{}': {}", template, error))
 }
 
 
-pub fn get_data( template : &Option<String> ) -> Option<String> {
-     match template {
-        Some(template) => {
-             Some(file_ops::get_file(template))
-        },
-        None => None
-    }
+/*
+   Reads and parses the data file, if one was given.
+*/
+pub fn get_data( data : &Option<String> ) -> Result<Option<Value>, String> {
+    let path = match data {
+        Some(path) => path,
+        None => return Ok(None)
+    };
+    let source = file_ops::read_file(Path::new(path))
+        .map_err(|error| format!("Slabkit error: couldn't read the data file '{}': {}", path, error))?;
+    serde_json::from_str(&source)
+        .map(Some)
+        .map_err(|error| format!("Slabkit error: couldn't parse the data file '{}': {}", path, error))
 }
 
-pub fn get_outfile( output : &Option<String> ) -> String {
-    let logger = Logger::new(
-        "INFO".to_string(),
-        "Getting output file".to_string(),
-    );
-    match output {
-        Some(output) => {
-           file_ops::get_file(output)
-        },
-        None => {
-            logger.error("No output file provided");
-            String::from("output.html")
-        }
-    }
-}
\ No newline at end of file
+/*
+   The path to write the output to, or None for stdout (`-o -` or no `-o` at all).
+*/
+pub fn get_outfile( output : &Option<String> ) -> Option<String> {
+    output.clone().filter(|output| output != "-")
+}